@@ -0,0 +1,278 @@
+use crate::{
+    encode::edit::join_step,
+    light::{ProtoTag, ProtoValue, WireType},
+    utils::time::unix_rfc3339,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Epochs that a numeric value may be counted from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Epoch {
+    /**Seconds since 1970-01-01 */
+    UnixSeconds,
+    /**Milliseconds since 1970-01-01 */
+    UnixMilliseconds,
+    /**Microseconds since 1970-01-01 */
+    UnixMicroseconds,
+    /**Nanoseconds since 1970-01-01 */
+    UnixNanoseconds,
+    /**Seconds since 2001-01-01. Also known as Mac absolute time */
+    Cocoa,
+    /**Microseconds since 1601-01-01. Used by `WebKit` and Chrome */
+    WebKit,
+    /**100 nanosecond intervals since 1601-01-01 */
+    FileTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimestampOptions {
+    /**Earliest plausible timestamp in Unix seconds */
+    pub start: i64,
+    /**Latest plausible timestamp in Unix seconds */
+    pub end: i64,
+    /**Epochs to check */
+    pub epochs: Vec<Epoch>,
+}
+
+impl Default for TimestampOptions {
+    /// Check all epochs for timestamps between 2005-01-01 and 2040-01-01. Starting a few years after the Cocoa epoch keeps small counters from being flagged
+    fn default() -> Self {
+        TimestampOptions {
+            start: 1104537600,
+            end: 2208988800,
+            epochs: vec![
+                Epoch::UnixSeconds,
+                Epoch::UnixMilliseconds,
+                Epoch::UnixMicroseconds,
+                Epoch::UnixNanoseconds,
+                Epoch::Cocoa,
+                Epoch::WebKit,
+                Epoch::FileTime,
+            ],
+        }
+    }
+}
+
+/// A value that looks like a timestamp
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimestampAnnotation {
+    /**Path to the value, such as `2.1[1].5`. Can be used with `FieldPath::parse` */
+    pub path: String,
+    pub wire_type: WireType,
+    pub epoch: Epoch,
+    /**ISO 8601 timestamp in UTC */
+    pub timestamp: String,
+}
+
+/// Seconds between 1601-01-01 and 1970-01-01
+const WINDOWS_EPOCH: i64 = 11644473600;
+/// Seconds between 1970-01-01 and 2001-01-01
+const COCOA_EPOCH: i64 = 978307200;
+
+/// Look for numeric values that are plausible timestamps. A value may match more than one epoch
+///
+/// # Example
+/// ```rust
+/// use sunlight::annotate::timestamp::{Epoch, TimestampOptions, annotate_timestamps};
+///
+/// // Field 4 is a double containing Mac absolute time
+/// let proto_bytes = [33, 217, 236, 52, 46, 208, 118, 198, 65];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// let result = annotate_timestamps(&proto_map, &TimestampOptions::default());
+/// assert_eq!(result[0].path, "4");
+/// assert_eq!(result[0].epoch, Epoch::Cocoa);
+/// assert_eq!(result[0].timestamp, "2024-11-20T04:43:08.413478Z");
+/// ```
+pub fn annotate_timestamps(
+    proto_map: &HashMap<usize, ProtoTag>,
+    options: &TimestampOptions,
+) -> Vec<TimestampAnnotation> {
+    let mut annotations = Vec::new();
+    annotate_fields(proto_map, "", options, &mut annotations);
+    annotations
+}
+
+/// Check each field of a message. Sub-messages are checked too
+fn annotate_fields(
+    proto_map: &HashMap<usize, ProtoTag>,
+    parent: &str,
+    options: &TimestampOptions,
+    annotations: &mut Vec<TimestampAnnotation>,
+) {
+    let mut fields: Vec<&ProtoTag> = proto_map.values().collect();
+    fields.sort_by_key(|proto_tag| proto_tag.tag.field);
+    for proto_tag in fields {
+        let field = proto_tag.tag.field;
+        let values = proto_tag.value.values();
+        let repeated = values.len() > 1;
+
+        for (index, entry) in values.iter().enumerate() {
+            let path = join_step(parent, field, repeated.then_some(index));
+
+            if let ProtoValue::Message(sub) = entry {
+                annotate_fields(sub, &path, options, annotations);
+                continue;
+            }
+
+            for (epoch, seconds, nanos) in candidates(entry, options) {
+                if seconds < options.start || seconds > options.end {
+                    continue;
+                }
+                if let Some(timestamp) = unix_rfc3339(seconds, nanos) {
+                    annotations.push(TimestampAnnotation {
+                        path: path.clone(),
+                        wire_type: entry.wire_type(),
+                        epoch,
+                        timestamp,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Convert a value to Unix seconds and nanoseconds for each epoch it could use
+fn candidates(value: &ProtoValue, options: &TimestampOptions) -> Vec<(Epoch, i64, u32)> {
+    let (integer, float) = match value {
+        ProtoValue::VarInt(number) => (*number as i64, None),
+        ProtoValue::Fixed64(bits) => (*bits as i64, Some(f64::from_bits(*bits))),
+        ProtoValue::Fixed32(bits) => (i64::from(*bits), Some(f32::from_bits(*bits) as f64)),
+        _ => return Vec::new(),
+    };
+
+    let mut results = Vec::new();
+    for epoch in &options.epochs {
+        if let Some((seconds, nanos)) = from_integer(*epoch, integer) {
+            results.push((*epoch, seconds, nanos));
+        }
+        if let Some(number) = float
+            && let Some((seconds, nanos)) = from_float(*epoch, number)
+        {
+            results.push((*epoch, seconds, nanos));
+        }
+    }
+    results
+}
+
+/// Convert an integer timestamp to Unix seconds and nanoseconds
+fn from_integer(epoch: Epoch, number: i64) -> Option<(i64, u32)> {
+    let (units_per_second, offset) = match epoch {
+        Epoch::UnixSeconds => (1, 0),
+        Epoch::UnixMilliseconds => (1_000, 0),
+        Epoch::UnixMicroseconds => (1_000_000, 0),
+        Epoch::WebKit => (1_000_000, -WINDOWS_EPOCH),
+        Epoch::UnixNanoseconds => (1_000_000_000, 0),
+        Epoch::Cocoa => (1, COCOA_EPOCH),
+        Epoch::FileTime => (10_000_000, -WINDOWS_EPOCH),
+    };
+
+    let seconds = number.div_euclid(units_per_second).checked_add(offset)?;
+    let nanos = number.rem_euclid(units_per_second) * (1_000_000_000 / units_per_second);
+    Some((seconds, u32::try_from(nanos).ok()?))
+}
+
+/// Convert a floating point timestamp to Unix seconds and nanoseconds. Only seconds and milliseconds are commonly stored as floats
+fn from_float(epoch: Epoch, number: f64) -> Option<(i64, u32)> {
+    let (seconds, offset) = match epoch {
+        Epoch::UnixSeconds => (number, 0),
+        Epoch::UnixMilliseconds => (number / 1000.0, 0),
+        Epoch::Cocoa => (number, COCOA_EPOCH),
+        _ => return None,
+    };
+    // Must be well within the range of i64
+    if !seconds.is_finite() || seconds.abs() > 1e15 {
+        return None;
+    }
+
+    let whole = seconds.floor();
+    // Doubles only keep about microsecond precision for current dates
+    let micros = ((seconds - whole) * 1_000_000.0).round() as u32;
+    let (whole, micros) = if micros == 1_000_000 {
+        (whole + 1.0, 0)
+    } else {
+        (whole, micros)
+    };
+    Some(((whole as i64).checked_add(offset)?, micros * 1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Epoch, TimestampOptions, annotate_timestamps, from_float, from_integer};
+    use crate::light::{WireType, extract_protobuf};
+
+    #[test]
+    fn test_annotate_timestamps() {
+        let test = [
+            16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46, 100,
+            117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98, 114, 111,
+            119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = annotate_timestamps(&proto_map, &TimestampOptions::default());
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].path, "4");
+        assert_eq!(result[0].wire_type, WireType::Fixed64);
+        assert_eq!(result[0].epoch, Epoch::Cocoa);
+        assert_eq!(result[0].timestamp, "2024-11-20T04:43:08.413478Z");
+    }
+
+    #[test]
+    fn test_annotate_timestamps_nested() {
+        // Field 2 is a sub-message with Unix milliseconds, field 3 is repeated Unix seconds
+        let test = [
+            18, 7, 8, 128, 168, 223, 219, 181, 50, 24, 128, 217, 137, 186, 6, 24, 1,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let options = TimestampOptions {
+            epochs: vec![Epoch::UnixSeconds, Epoch::UnixMilliseconds],
+            ..Default::default()
+        };
+        let result = annotate_timestamps(&proto_map, &options);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].path, "2.1");
+        assert_eq!(result[0].epoch, Epoch::UnixMilliseconds);
+        assert_eq!(result[1].path, "3[0]");
+        assert_eq!(result[1].timestamp, "2024-11-24T00:00:00Z");
+    }
+
+    #[test]
+    fn test_annotate_timestamps_range() {
+        let test = [24, 128, 217, 137, 186, 6];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let options = TimestampOptions {
+            end: 1700000000,
+            ..Default::default()
+        };
+        assert!(annotate_timestamps(&proto_map, &options).is_empty());
+    }
+
+    #[test]
+    fn test_from_integer() {
+        assert_eq!(
+            from_integer(Epoch::WebKit, 13377043200000000).unwrap(),
+            (1732569600, 0)
+        );
+        assert_eq!(
+            from_integer(Epoch::FileTime, 133770432001234567).unwrap(),
+            (1732569600, 123456700)
+        );
+        assert_eq!(
+            from_integer(Epoch::UnixNanoseconds, 1732406400000000001).unwrap(),
+            (1732406400, 1)
+        );
+        assert_eq!(from_integer(Epoch::Cocoa, 0).unwrap(), (978307200, 0));
+    }
+
+    #[test]
+    fn test_from_float() {
+        assert_eq!(
+            from_float(Epoch::UnixSeconds, 1732406400.25).unwrap(),
+            (1732406400, 250000000)
+        );
+        assert!(from_float(Epoch::UnixSeconds, f64::NAN).is_none());
+        assert!(from_float(Epoch::FileTime, 1.5).is_none());
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{
+    delimited::iter_delimited,
+    error::SunlightError,
+    light::{ExtractOptions, extract_protobuf_options},
+    render::json::{JsonOptions, proto_to_json},
+};
+use log::{error, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs::{read, read_dir},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Options to control how files are decoded in batch mode
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /**Each file is a stream of length delimited messages instead of a single message */
+    pub delimited: bool,
+    /**How messages are decoded. Set `arrays` to keep the same record shape across messages */
+    pub extract: ExtractOptions,
+    /**How decoded messages are converted to JSON */
+    pub json: JsonOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordStatus {
+    Ok,
+    Error,
+}
+
+/// Result of decoding one message. Written as one line of JSON Lines output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchRecord {
+    /**File the message was read from */
+    pub path: String,
+    /**Offset to the message in the file. Always 0 unless the file is delimited */
+    pub offset: usize,
+    pub status: RecordStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A file found by `collect_files`
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalkEntry {
+    File(PathBuf),
+    /**Subdirectory that could not be read */
+    Unreadable {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+impl WalkEntry {
+    pub fn path(&self) -> &Path {
+        match self {
+            WalkEntry::File(path) | WalkEntry::Unreadable { path, .. } => path,
+        }
+    }
+}
+
+/// Totals for a batch run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchSummary {
+    pub files: usize,
+    pub records: usize,
+    /**Records that could not be decoded */
+    pub failures: usize,
+}
+
+impl BatchRecord {
+    fn ok(path: &str, offset: usize, message: Value) -> BatchRecord {
+        BatchRecord {
+            path: path.to_string(),
+            offset,
+            status: RecordStatus::Ok,
+            message: Some(message),
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(path: &str, offset: usize, err: &dyn std::fmt::Display) -> BatchRecord {
+        warn!("[sunlight] could not decode {path} at offset {offset}: {err}");
+        BatchRecord {
+            path: path.to_string(),
+            offset,
+            status: RecordStatus::Error,
+            message: None,
+            error: Some(err.to_string()),
+        }
+    }
+}
+
+/// Decode every file under a directory and write one JSON Lines record per message. Files and records that cannot be decoded are written as error records and do not stop the run
+///
+/// # Example
+/// ```rust
+/// use sunlight::batch::{BatchOptions, batch_directory};
+///
+/// let mut output = Vec::new();
+/// let summary = batch_directory(
+///     std::path::Path::new("tests/test_data/blackboxprotobuf"),
+///     &BatchOptions::default(),
+///     &mut output,
+/// )
+/// .unwrap();
+/// assert_eq!(summary.files, 2);
+/// assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+/// ```
+pub fn batch_directory<W: Write>(
+    directory: &Path,
+    options: &BatchOptions,
+    writer: &mut W,
+) -> Result<BatchSummary, SunlightError> {
+    let mut summary = BatchSummary::default();
+    for entry in collect_files(directory)? {
+        if matches!(entry, WalkEntry::File(_)) {
+            summary.files += 1;
+        }
+        write_records(&decode_entry(&entry, options), &mut summary, writer)?;
+    }
+    Ok(summary)
+}
+
+/// Read and decode a file found by `collect_files`. A subdirectory that could not be read is returned as an error record
+pub(crate) fn decode_entry(entry: &WalkEntry, options: &BatchOptions) -> Vec<BatchRecord> {
+    let name = entry.path().to_string_lossy();
+    match entry {
+        WalkEntry::File(path) => match read(path) {
+            Ok(result) => decode_buffer(&name, &result, options),
+            Err(err) => vec![BatchRecord::failed(&name, 0, &err)],
+        },
+        WalkEntry::Unreadable { error, .. } => vec![BatchRecord::failed(&name, 0, error)],
+    }
+}
+
+/// Decode a single buffer into batch records. A delimited buffer returns one record per message
+pub fn decode_buffer(path: &str, data: &[u8], options: &BatchOptions) -> Vec<BatchRecord> {
+    if !options.delimited {
+        return vec![decode_record(path, 0, data, options)];
+    }
+
+    let mut records = Vec::new();
+    let mut stream = iter_delimited(data);
+    while let Some(record) = stream.next() {
+        let result = match record {
+            Ok(result) => decode_record(path, result.offset, result.data, options),
+            // The remaining bytes cannot be split into records
+            Err(err) => BatchRecord::failed(path, stream.offset(), &err),
+        };
+        records.push(result);
+    }
+    records
+}
+
+/// Decode one message into a batch record
+pub fn decode_record(
+    path: &str,
+    offset: usize,
+    data: &[u8],
+    options: &BatchOptions,
+) -> BatchRecord {
+    match extract_protobuf_options(data, &options.extract) {
+        Ok(result) => BatchRecord::ok(path, offset, proto_to_json(&result, &options.json)),
+        Err(err) => BatchRecord::failed(path, offset, &err),
+    }
+}
+
+/// Write records as JSON Lines and update the totals
+pub(crate) fn write_records<W: Write>(
+    records: &[BatchRecord],
+    summary: &mut BatchSummary,
+    writer: &mut W,
+) -> Result<(), SunlightError> {
+    for record in records {
+        summary.records += 1;
+        if record.status == RecordStatus::Error {
+            summary.failures += 1;
+        }
+        let line = serde_json::to_string(record).map_err(|err| {
+            error!("[sunlight] could not serialize batch record: {err:?}");
+            SunlightError::Io
+        })?;
+        if let Err(err) = writeln!(writer, "{line}") {
+            error!("[sunlight] could not write batch record: {err:?}");
+            return Err(SunlightError::Io);
+        }
+    }
+    Ok(())
+}
+
+/// Recursively list every file under a directory in sorted order. Subdirectories that cannot be read are returned as `WalkEntry::Unreadable`
+/// Symbolic links to directories are skipped so a link back to a parent directory cannot loop forever
+pub fn collect_files(directory: &Path) -> Result<Vec<WalkEntry>, SunlightError> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+    let mut is_root = true;
+    while let Some(current) = directories.pop() {
+        let entries = match read_dir(&current) {
+            Ok(result) => result,
+            Err(err) if is_root => {
+                error!("[sunlight] could not read {}: {err:?}", current.display());
+                return Err(SunlightError::Io);
+            }
+            Err(err) => {
+                files.push(WalkEntry::Unreadable {
+                    path: current,
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        is_root = false;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // File type of the entry itself. Symbolic links are not followed
+            let Ok(file_type) = entry.file_type() else {
+                files.push(WalkEntry::File(path));
+                continue;
+            };
+            if file_type.is_dir() {
+                directories.push(path);
+            } else if file_type.is_symlink() && path.is_dir() {
+                warn!("[sunlight] skipping linked directory {}", path.display());
+            } else {
+                files.push(WalkEntry::File(path));
+            }
+        }
+    }
+    files.sort_by(|first, second| first.path().cmp(second.path()));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BatchOptions, RecordStatus, WalkEntry, batch_directory, collect_files, decode_buffer,
+        decode_entry,
+    };
+    use std::path::PathBuf;
+
+    fn test_data() -> PathBuf {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data");
+        test_path
+    }
+
+    #[test]
+    fn test_batch_directory() {
+        let mut output = Vec::new();
+        let summary = batch_directory(&test_data(), &BatchOptions::default(), &mut output).unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.records, 2);
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(
+            lines[1]["path"]
+                .as_str()
+                .unwrap()
+                .ends_with("test_message.out")
+        );
+        assert_eq!(lines[1]["status"], "ok");
+        assert_eq!(lines[1]["offset"], 0);
+        assert!(lines[1]["message"].is_object());
+
+        assert!(
+            batch_directory(
+                &test_data().join("missing"),
+                &BatchOptions::default(),
+                &mut Vec::new()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_decode_buffer() {
+        // The second record is not a message and the third is missing a byte
+        let test = [2, 8, 1, 2, 18, 5, 3, 18, 1];
+        let options = BatchOptions {
+            delimited: true,
+            ..Default::default()
+        };
+        let records = decode_buffer("test", &test, &options);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].status, RecordStatus::Ok);
+        assert_eq!(records[1].status, RecordStatus::Error);
+        assert_eq!(records[1].offset, 3);
+        assert_eq!(records[2].status, RecordStatus::Error);
+        assert_eq!(records[2].offset, 6);
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let files = collect_files(&test_data()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].path().ends_with("blackboxprotobuf/LICENSE"));
+        assert!(matches!(files[0], WalkEntry::File(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_symlink_loop() {
+        let mut directory = std::env::temp_dir();
+        directory.push(format!("sunlight_walk_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("sub")).unwrap();
+        std::fs::write(directory.join("sub/data.bin"), [8, 1]).unwrap();
+        // Link back to the parent directory
+        std::os::unix::fs::symlink(&directory, directory.join("sub/parent")).unwrap();
+
+        let files = collect_files(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(files, [WalkEntry::File(directory.join("sub/data.bin"))]);
+    }
+
+    #[test]
+    fn test_decode_entry_unreadable() {
+        let entry = WalkEntry::Unreadable {
+            path: PathBuf::from("data/private"),
+            error: String::from("permission denied"),
+        };
+        let records = decode_entry(&entry, &BatchOptions::default());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].path, "data/private");
+        assert_eq!(records[0].status, RecordStatus::Error);
+    }
+}
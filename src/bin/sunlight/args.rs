@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+pub(crate) const USAGE: &str = "Decode Protobuf binary data without a schema
+
+Usage: sunlight [OPTIONS] [FILE]
+       sunlight batch [BATCH OPTIONS] <DIRECTORY>
+
+Reads from stdin if FILE is missing or -
+
+Options:
+  -x, --hex <HEX>          Decode a hex string instead of a file
+  -b, --base64 <BASE64>    Decode a base64 string instead of a file
+  -f, --format <FORMAT>    Output format: json, pretty, text, hexdump or html [default: pretty]
+  -d, --delimited          Input is a stream of varint length delimited messages
+  -o, --offset <OFFSET>    Skip bytes before decoding. Accepts decimal or 0x hex [default: 0]
+  -l, --length <LENGTH>    Only decode this many bytes after the offset
+      --lenient            Keep going after errors and decode as much as possible
+  -a, --always-array       Output every field as an array, even if it is only seen once
+      --mmap               Map FILE into memory instead of reading it. The file must not change
+                           while it is decoded. Requires the mmap feature
+  -h, --help               Print help
+  -V, --version            Print version
+
+Batch mode decodes every file under DIRECTORY and writes one JSON Lines record per message
+
+Batch options:
+  -d, --delimited          Files are streams of varint length delimited messages
+  -O, --output <FILE>      Write records to a file instead of stdout
+  -j, --threads <THREADS>  Decode files in parallel. 0 uses one thread per CPU. Requires the parallel feature
+  -a, --always-array       Output every field as an array so every record has the same shape";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Input {
+    File(PathBuf),
+    Stdin,
+    Hex(String),
+    Base64(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Format {
+    Json,
+    Pretty,
+    Text,
+    Hexdump,
+    Html,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Options {
+    pub(crate) input: Input,
+    pub(crate) format: Format,
+    pub(crate) delimited: bool,
+    pub(crate) offset: usize,
+    pub(crate) length: Option<usize>,
+    pub(crate) lenient: bool,
+    pub(crate) always_array: bool,
+    pub(crate) mmap: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct BatchArgs {
+    pub(crate) directory: PathBuf,
+    pub(crate) delimited: bool,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) always_array: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    Decode(Options),
+    Batch(BatchArgs),
+    Help,
+    Version,
+}
+
+/// Parse the command line arguments. The program name should not be included
+pub(crate) fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_some_and(|arg| arg == "batch") {
+        args.next();
+        return parse_batch_args(args);
+    }
+
+    let mut options = Options {
+        input: Input::Stdin,
+        format: Format::Pretty,
+        delimited: false,
+        offset: 0,
+        length: None,
+        lenient: false,
+        always_array: false,
+        mmap: false,
+    };
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-x" | "--hex" => options.input = Input::Hex(value(&arg)?),
+            "-b" | "--base64" => options.input = Input::Base64(value(&arg)?),
+            "-f" | "--format" => {
+                options.format = match value(&arg)?.as_str() {
+                    "json" => Format::Json,
+                    "pretty" => Format::Pretty,
+                    "text" => Format::Text,
+                    "hexdump" => Format::Hexdump,
+                    "html" => Format::Html,
+                    format => return Err(format!("unknown format {format}")),
+                }
+            }
+            "-d" | "--delimited" => options.delimited = true,
+            "-o" | "--offset" => options.offset = parse_number(&value(&arg)?)?,
+            "-l" | "--length" => options.length = Some(parse_number(&value(&arg)?)?),
+            "--lenient" => options.lenient = true,
+            "-a" | "--always-array" => options.always_array = true,
+            "--mmap" => {
+                if !cfg!(feature = "mmap") {
+                    return Err(format!("{arg} requires the mmap feature"));
+                }
+                options.mmap = true;
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {arg}"));
+            }
+            _ if file.is_some() => return Err(format!("unexpected argument {arg}")),
+            _ => file = Some(arg),
+        }
+    }
+
+    if let Some(path) = file {
+        if !matches!(options.input, Input::Stdin) {
+            return Err(String::from("a file cannot be used with --hex or --base64"));
+        }
+        if path != "-" {
+            options.input = Input::File(PathBuf::from(path));
+        }
+    }
+    Ok(Command::Decode(options))
+}
+
+/// Parse the arguments after the `batch` subcommand
+fn parse_batch_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut delimited = false;
+    let mut output = None;
+    let mut threads = None;
+    let mut always_array = false;
+    let mut directory = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-d" | "--delimited" => delimited = true,
+            "-O" | "--output" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {arg}"))?;
+                output = Some(PathBuf::from(value));
+            }
+            "-j" | "--threads" => {
+                if !cfg!(feature = "parallel") {
+                    return Err(format!("{arg} requires the parallel feature"));
+                }
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {arg}"))?;
+                threads = Some(parse_number(&value)?);
+            }
+            "-a" | "--always-array" => always_array = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if directory.is_some() => return Err(format!("unexpected argument {arg}")),
+            _ => directory = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let directory = directory.ok_or_else(|| String::from("batch requires a directory"))?;
+    Ok(Command::Batch(BatchArgs {
+        directory,
+        delimited,
+        output,
+        threads,
+        always_array,
+    }))
+}
+
+/// Parse a decimal or `0x` hex number
+fn parse_number(value: &str) -> Result<usize, String> {
+    let result = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    result.map_err(|_err| format!("invalid number {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchArgs, Command, Format, Input, Options, parse_args};
+    use std::path::PathBuf;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let result = parse_args(args(&[
+            "-f",
+            "text",
+            "--delimited",
+            "-o",
+            "0x10",
+            "--length",
+            "32",
+            "--lenient",
+            "-a",
+            "data.bin",
+        ]))
+        .unwrap();
+        assert_eq!(
+            result,
+            Command::Decode(Options {
+                input: Input::File(PathBuf::from("data.bin")),
+                format: Format::Text,
+                delimited: true,
+                offset: 16,
+                length: Some(32),
+                lenient: true,
+                always_array: true,
+                mmap: false,
+            })
+        );
+
+        let Command::Decode(options) = parse_args(args(&["--hex", "0801"])).unwrap() else {
+            panic!("expected decode options");
+        };
+        assert_eq!(options.input, Input::Hex(String::from("0801")));
+        assert_eq!(options.format, Format::Pretty);
+        assert!(!options.always_array);
+        assert!(!options.mmap);
+
+        let Command::Decode(options) = parse_args(args(&["-"])).unwrap() else {
+            panic!("expected decode options");
+        };
+        assert_eq!(options.input, Input::Stdin);
+        assert_eq!(parse_args(args(&["-h"])).unwrap(), Command::Help);
+    }
+
+    #[test]
+    fn test_parse_batch_args() {
+        let result = parse_args(args(&[
+            "batch",
+            "-d",
+            "blobs",
+            "--output",
+            "out.jsonl",
+            "--always-array",
+        ]))
+        .unwrap();
+        assert_eq!(
+            result,
+            Command::Batch(BatchArgs {
+                directory: PathBuf::from("blobs"),
+                delimited: true,
+                output: Some(PathBuf::from("out.jsonl")),
+                threads: None,
+                always_array: true,
+            })
+        );
+        assert!(parse_args(args(&["batch"])).is_err());
+        assert!(parse_args(args(&["batch", "--format", "text", "blobs"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(args(&["--format", "xml"])).is_err());
+        assert!(parse_args(args(&["--offset"])).is_err());
+        assert!(parse_args(args(&["--hex", "08", "data.bin"])).is_err());
+        assert!(parse_args(args(&["--unknown"])).is_err());
+        assert!(parse_args(args(&["a.bin", "b.bin"])).is_err());
+        assert_eq!(
+            parse_args(args(&["--mmap", "data.bin"])).is_ok(),
+            cfg!(feature = "mmap")
+        );
+    }
+}
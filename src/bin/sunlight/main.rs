@@ -0,0 +1,372 @@
+mod args;
+
+use args::{BatchArgs, Command, Format, Input, Options, USAGE, parse_args};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write, stdin, stdout},
+    path::Path,
+    process::exit,
+};
+#[cfg(feature = "mmap")]
+use sunlight::mmap::MappedFile;
+#[cfg(feature = "parallel")]
+use sunlight::parallel::{ParallelOptions, batch_directory_parallel};
+use sunlight::{
+    batch::{BatchOptions, BatchSummary, batch_directory},
+    delimited::iter_delimited,
+    light::{ArrayMode, ExtractOptions, extract_protobuf_options},
+    render::{
+        hexdump::{hexdump_html, hexdump_text},
+        json::{JsonOptions, proto_to_json},
+        text::proto_to_text,
+    },
+    view::iter_fields,
+};
+
+fn main() {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            exit(2);
+        }
+    };
+
+    let options = match command {
+        Command::Decode(options) => options,
+        Command::Batch(batch) => {
+            if let Err(err) = run_batch(&batch) {
+                eprintln!("error: {err}");
+                exit(1);
+            }
+            return;
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            return;
+        }
+        Command::Version => {
+            println!("sunlight {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+    };
+
+    let output = read_input(&options.input, options.mmap).and_then(|data| {
+        let data = slice_input((*data).as_ref(), options.offset, options.length)?;
+        if options.delimited {
+            decode_delimited(data, &options)
+        } else {
+            decode_message(data, &options)
+        }
+    });
+
+    match output {
+        Ok(result) => {
+            let mut writer = stdout().lock();
+            if let Err(err) = writer
+                .write_all(result.as_bytes())
+                .and_then(|()| writer.flush())
+            {
+                eprintln!("error: could not write output: {err}");
+                exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            exit(1);
+        }
+    }
+}
+
+/// Decode every file in a directory to JSON Lines
+fn run_batch(batch: &BatchArgs) -> Result<(), String> {
+    let options = BatchOptions {
+        delimited: batch.delimited,
+        extract: extract_options(batch.always_array),
+        ..Default::default()
+    };
+    let mut writer: Box<dyn Write> = match &batch.output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|err| {
+                format!("could not create {}: {err}", path.display())
+            })?))
+        }
+        None => Box::new(stdout().lock()),
+    };
+    let summary = decode_directory(batch, &options, &mut writer)?;
+    writer.flush().map_err(|err| err.to_string())?;
+
+    eprintln!(
+        "decoded {} records from {} files. {} records failed",
+        summary.records, summary.files, summary.failures
+    );
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+fn decode_directory(
+    batch: &BatchArgs,
+    options: &BatchOptions,
+    writer: &mut Box<dyn Write>,
+) -> Result<BatchSummary, String> {
+    let result = match batch.threads {
+        Some(threads) => {
+            let parallel = ParallelOptions {
+                threads,
+                ..Default::default()
+            };
+            batch_directory_parallel(&batch.directory, options, &parallel, writer)
+        }
+        None => batch_directory(&batch.directory, options, writer),
+    };
+    result.map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "parallel"))]
+fn decode_directory(
+    batch: &BatchArgs,
+    options: &BatchOptions,
+    writer: &mut Box<dyn Write>,
+) -> Result<BatchSummary, String> {
+    batch_directory(&batch.directory, options, writer).map_err(|err| err.to_string())
+}
+
+/// Read all bytes from the requested input. Files are memory mapped if `mmap` is set
+fn read_input(input: &Input, mmap: bool) -> Result<Box<dyn AsRef<[u8]>>, String> {
+    let data = match input {
+        Input::File(path) if mmap => return map_file(path),
+        Input::File(path) => std::fs::read(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?,
+        Input::Stdin => {
+            let mut data = Vec::new();
+            stdin()
+                .read_to_end(&mut data)
+                .map_err(|err| format!("could not read stdin: {err}"))?;
+            data
+        }
+        Input::Hex(value) => decode_hex(value)?,
+        Input::Base64(value) => STANDARD
+            .decode(value.trim())
+            .map_err(|err| format!("invalid base64: {err}"))?,
+    };
+    Ok(Box::new(data))
+}
+
+/// Map the input file into memory. The file must not be changed while it is decoded
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> Result<Box<dyn AsRef<[u8]>>, String> {
+    // SAFETY: Only reached when the user passes --mmap, whose help text states the `MappedFile::open` contract that the file must not change while mapped
+    let file = unsafe { MappedFile::open(path) }
+        .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+    Ok(Box::new(file))
+}
+
+/// `--mmap` is rejected by the argument parser without the mmap feature
+#[cfg(not(feature = "mmap"))]
+fn map_file(path: &Path) -> Result<Box<dyn AsRef<[u8]>>, String> {
+    Err(format!(
+        "could not map {}: the mmap feature is disabled",
+        path.display()
+    ))
+}
+
+/// Decode a hex string. Whitespace is ignored
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = value
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("hex string has an odd number of digits"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| String::from("invalid hex string"))
+        })
+        .collect()
+}
+
+/// Get the bytes between offset and offset + length
+fn slice_input(data: &[u8], offset: usize, length: Option<usize>) -> Result<&[u8], String> {
+    let remaining = data
+        .get(offset..)
+        .ok_or_else(|| format!("offset {offset} is past the end of the input"))?;
+    match length {
+        Some(length) => remaining
+            .get(..length)
+            .ok_or_else(|| format!("length {length} is past the end of the input")),
+        None => Ok(remaining),
+    }
+}
+
+/// Decode the input as a single message
+fn decode_message(data: &[u8], options: &Options) -> Result<String, String> {
+    let data = if options.lenient {
+        valid_prefix(data)
+    } else {
+        data
+    };
+    render(data, options).map_err(|err| format!("could not decode message: {err}"))
+}
+
+/// Decode the input as a stream of length delimited messages
+fn decode_delimited(data: &[u8], options: &Options) -> Result<String, String> {
+    let mut messages = Vec::new();
+    let mut output = String::new();
+    for record in iter_delimited(data) {
+        let record = match record {
+            Ok(result) => result,
+            Err(err) if options.lenient => {
+                eprintln!("warning: stopping at truncated record: {err:?}");
+                break;
+            }
+            Err(err) => return Err(format!("could not read delimited record: {err:?}")),
+        };
+
+        let offset = record.offset + options.offset;
+        let result = match options.format {
+            Format::Json | Format::Pretty => message_json(record.data, options).map(|message| {
+                messages.push(json!({"offset": offset, "message": message}));
+            }),
+            Format::Text | Format::Hexdump | Format::Html => {
+                render(record.data, options).map(|rendered| {
+                    output.push_str(&format!("# record at offset {offset}\n"));
+                    output.push_str(&rendered);
+                })
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(err) if options.lenient => {
+                eprintln!("warning: skipping record at offset {offset}: {err}");
+            }
+            Err(err) => return Err(format!("could not decode record at offset {offset}: {err}")),
+        }
+    }
+
+    match options.format {
+        Format::Json => Ok(format!("{}\n", Value::Array(messages))),
+        Format::Pretty => serde_json::to_string_pretty(&Value::Array(messages))
+            .map(|result| format!("{result}\n"))
+            .map_err(|err| err.to_string()),
+        Format::Text | Format::Hexdump | Format::Html => Ok(output),
+    }
+}
+
+/// Render a single message in the requested format
+fn render(data: &[u8], options: &Options) -> Result<String, String> {
+    let error = |err| format!("{err:?}");
+    match options.format {
+        Format::Json | Format::Pretty => {
+            let value = message_json(data, options)?;
+            let result = if options.format == Format::Pretty {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            result
+                .map(|result| format!("{result}\n"))
+                .map_err(|err| err.to_string())
+        }
+        Format::Text => extract_protobuf_options(data, &extract_options(options.always_array))
+            .map(|proto_map| proto_to_text(&proto_map))
+            .map_err(error),
+        Format::Hexdump => hexdump_text(data).map_err(error),
+        Format::Html => hexdump_html(data).map_err(error),
+    }
+}
+
+/// Decode a single message to JSON
+fn message_json(data: &[u8], options: &Options) -> Result<Value, String> {
+    extract_protobuf_options(data, &extract_options(options.always_array))
+        .map(|proto_map| proto_to_json(&proto_map, &JsonOptions::default()))
+        .map_err(|err| format!("{err:?}"))
+}
+
+/// Decoding options shared by single message and batch mode
+fn extract_options(always_array: bool) -> ExtractOptions {
+    let arrays = if always_array {
+        ArrayMode::Always
+    } else {
+        ArrayMode::Seen
+    };
+    ExtractOptions {
+        arrays,
+        ..Default::default()
+    }
+}
+
+/// Get the longest prefix of the data that contains only complete fields
+fn valid_prefix(data: &[u8]) -> &[u8] {
+    let mut length = 0;
+    for field in iter_fields(data) {
+        match field {
+            Ok(result) => length += result.raw().len(),
+            Err(_) => {
+                eprintln!("warning: only decoding the first {length} bytes");
+                break;
+            }
+        }
+    }
+    &data[..length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_delimited, decode_hex, slice_input, valid_prefix};
+    use crate::args::{Format, Input, Options};
+    use serde_json::Value;
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("08 96 01").unwrap(), [8, 150, 1]);
+        assert!(decode_hex("089").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_slice_input() {
+        let test = [1, 2, 3, 4];
+        assert_eq!(slice_input(&test, 1, Some(2)).unwrap(), [2, 3]);
+        assert!(slice_input(&test, 4, None).unwrap().is_empty());
+        assert!(slice_input(&test, 5, None).is_err());
+        assert!(slice_input(&test, 2, Some(3)).is_err());
+    }
+
+    #[test]
+    fn test_valid_prefix() {
+        // The second field is missing a byte
+        let test = [8, 150, 1, 18, 5, 1];
+        assert_eq!(valid_prefix(&test), [8, 150, 1]);
+    }
+
+    #[test]
+    fn test_decode_delimited_json() {
+        let mut options = Options {
+            input: Input::Stdin,
+            format: Format::Json,
+            delimited: true,
+            offset: 0,
+            length: None,
+            lenient: false,
+            always_array: false,
+            mmap: false,
+        };
+        // The second record is a single string field and the third is not a message
+        let test = [2, 8, 1, 3, 18, 1, 97, 2, 18, 5];
+        assert!(decode_delimited(&test, &options).is_err());
+
+        options.lenient = true;
+        let result: Value =
+            serde_json::from_str(&decode_delimited(&test, &options).unwrap()).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 2);
+        assert_eq!(result[1]["offset"], 3);
+        assert_eq!(result[1]["message"]["2"]["value"], "a");
+    }
+}
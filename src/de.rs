@@ -0,0 +1,910 @@
+use crate::{
+    error::SunlightError,
+    tags::{
+        fixed::{parse_fixed32, parse_fixed64},
+        raw::RawValue,
+        var::{parse_varint, zigzag},
+    },
+    view::{FieldView, iter_fields},
+};
+use log::error;
+use serde::{
+    Deserialize,
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor, value::U32Deserializer},
+    forward_to_deserialize_any,
+};
+use std::collections::{HashMap, hash_map::Entry};
+
+/// Options to control how wire values are converted to Rust types
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeserializeOptions {
+    /**Decode signed integers stored as varints with zigzag encoding, like `sint32` and `sint64` */
+    pub zigzag: bool,
+}
+
+/// Deserialize Protobuf bytes into a type. Struct fields are matched by field number, so each field should be renamed to its number.
+/// Fields missing from the bytes use their default value if the struct allows it. Unknown fields are ignored
+///
+/// # Example
+/// ```rust
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Event {
+///     #[serde(rename = "1")]
+///     id: u64,
+///     #[serde(rename = "2")]
+///     name: String,
+///     #[serde(rename = "3")]
+///     tags: Vec<u32>,
+///     #[serde(rename = "6")]
+///     note: Option<String>,
+/// }
+///
+/// let proto_bytes = [8, 150, 1, 18, 3, 97, 98, 99, 26, 2, 1, 2, 32, 5];
+/// let event: Event = sunlight::de::from_bytes(&proto_bytes).unwrap();
+/// assert_eq!(event.id, 150);
+/// assert_eq!(event.name, "abc");
+/// assert_eq!(event.tags, [1, 2]);
+/// assert_eq!(event.note, None);
+/// ```
+pub fn from_bytes<'de, T: Deserialize<'de>>(data: &'de [u8]) -> Result<T, SunlightError> {
+    from_bytes_options(data, DeserializeOptions::default())
+}
+
+/// Deserialize Protobuf bytes into a type using the provided options
+pub fn from_bytes_options<'de, T: Deserialize<'de>>(
+    data: &'de [u8],
+    options: DeserializeOptions,
+) -> Result<T, SunlightError> {
+    T::deserialize(Deserializer::with_options(data, options))
+}
+
+/// Serde deserializer for a Protobuf message. Strings and bytes are borrowed from the input
+#[derive(Debug, Clone, Copy)]
+pub struct Deserializer<'de> {
+    data: &'de [u8],
+    options: DeserializeOptions,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn from_bytes(data: &'de [u8]) -> Self {
+        Deserializer::with_options(data, DeserializeOptions::default())
+    }
+
+    pub fn with_options(data: &'de [u8], options: DeserializeOptions) -> Self {
+        Deserializer { data, options }
+    }
+
+    /// Group the fields of the message by field number in the order they first appear
+    fn fields(&self) -> Result<MessageAccess<'de>, SunlightError> {
+        let mut fields: Vec<(usize, Vec<FieldView<'de>>)> = Vec::new();
+        let mut positions: HashMap<usize, usize> = HashMap::new();
+        for field in iter_fields(self.data) {
+            let field = field?;
+            match positions.entry(field.field()) {
+                Entry::Occupied(entry) => fields[*entry.get()].1.push(field),
+                Entry::Vacant(entry) => {
+                    entry.insert(fields.len());
+                    fields.push((field.field(), vec![field]));
+                }
+            }
+        }
+        Ok(MessageAccess {
+            fields: fields.into_iter(),
+            values: Vec::new(),
+            options: self.options,
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = SunlightError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(self.fields()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map struct enum identifier
+    }
+}
+
+/// Fields of a message passed to serde as a map keyed by field number
+struct MessageAccess<'de> {
+    fields: std::vec::IntoIter<(usize, Vec<FieldView<'de>>)>,
+    values: Vec<FieldView<'de>>,
+    options: DeserializeOptions,
+}
+
+impl<'de> MapAccess<'de> for MessageAccess<'de> {
+    type Error = SunlightError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((field, values)) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.values = values;
+        seed.deserialize(FieldKey(field)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(FieldValues {
+            values: std::mem::take(&mut self.values),
+            options: self.options,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+/// Field number used as a map key. Matches struct fields renamed to the number or integer map keys
+struct FieldKey(usize);
+
+impl<'de> de::Deserializer<'de> for FieldKey {
+    type Error = SunlightError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.0.to_string())
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0 as u64)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i128 u128 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Every value of one field. Scalars use the last value like other Protobuf parsers. Missing values use the Protobuf default
+struct FieldValues<'de> {
+    values: Vec<FieldView<'de>>,
+    options: DeserializeOptions,
+}
+
+impl<'de> FieldValues<'de> {
+    fn last(&self) -> Option<WireValue<'_, 'de>> {
+        self.values.last().map(|field| WireValue {
+            field: field.clone(),
+            repeated: None,
+            options: self.options,
+        })
+    }
+}
+
+/// Deserialize the last value or the default if the field is missing
+macro_rules! last_or_default {
+    ($($method:ident => $visit:ident($default:expr),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.last() {
+                    Some(value) => value.$method(visitor),
+                    None => visitor.$visit($default),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldValues<'de> {
+    type Error = SunlightError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.values.len() > 1 {
+            return self.deserialize_seq(visitor);
+        }
+        match self.last() {
+            Some(value) => value.deserialize_any(visitor),
+            None => visitor.visit_unit(),
+        }
+    }
+
+    last_or_default! {
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i64(0),
+        deserialize_i16 => visit_i64(0),
+        deserialize_i32 => visit_i64(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_u8 => visit_u64(0),
+        deserialize_u16 => visit_u64(0),
+        deserialize_u32 => visit_u64(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_f32 => visit_f32(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_str => visit_borrowed_str(""),
+        deserialize_string => visit_borrowed_str(""),
+        deserialize_bytes => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf => visit_borrowed_bytes(&[]),
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.last() {
+            Some(value) => value.deserialize_enum(name, variants, visitor),
+            None => visitor.visit_enum(U32Deserializer::<SunlightError>::new(0)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.values.is_empty() {
+            return visitor.visit_none();
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(RepeatedAccess {
+            values: self.values.into_iter(),
+            packed: &[],
+            finished: false,
+            options: self.options,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// Protobuf maps are repeated messages with the key in field 1 and the value in field 2
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(MapEntries {
+            entries: self.values.into_iter(),
+            value: None,
+            options: self.options,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.last() {
+            Some(value) => value.deserialize_struct(name, fields, visitor),
+            None => Deserializer::with_options(&[], self.options).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char unit_struct identifier
+    }
+}
+
+/// Values of a repeated field. Packed values are split into elements when a number is requested
+struct RepeatedAccess<'de> {
+    values: std::vec::IntoIter<FieldView<'de>>,
+    packed: &'de [u8],
+    /**Set when a number was requested but only empty packed values were left */
+    finished: bool,
+    options: DeserializeOptions,
+}
+
+impl<'de> SeqAccess<'de> for RepeatedAccess<'de> {
+    type Error = SunlightError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if !self.packed.is_empty() {
+            return seed
+                .deserialize(Packed {
+                    data: &mut self.packed,
+                    options: self.options,
+                })
+                .map(Some);
+        }
+        let Some(field) = self.values.next() else {
+            return Ok(None);
+        };
+        let options = self.options;
+        let result = seed.deserialize(WireValue {
+            field,
+            repeated: Some(self),
+            options,
+        });
+        if self.finished {
+            return Ok(None);
+        }
+        result.map(Some)
+    }
+}
+
+/// Entries of a Protobuf map field
+struct MapEntries<'de> {
+    entries: std::vec::IntoIter<FieldView<'de>>,
+    value: Option<FieldValues<'de>>,
+    options: DeserializeOptions,
+}
+
+impl<'de> MapAccess<'de> for MapEntries<'de> {
+    type Error = SunlightError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some(entry) = self.entries.next() else {
+            return Ok(None);
+        };
+        let payload = entry
+            .as_bytes()
+            .ok_or_else(|| mismatch(&entry, "map entry"))?;
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        for field in iter_fields(payload) {
+            let field = field?;
+            match field.field() {
+                1 => key.push(field),
+                2 => value.push(field),
+                _ => {}
+            }
+        }
+        self.value = Some(FieldValues {
+            values: value,
+            options: self.options,
+        });
+        seed.deserialize(FieldValues {
+            values: key,
+            options: self.options,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self.value.take().unwrap_or(FieldValues {
+            values: Vec::new(),
+            options: self.options,
+        });
+        seed.deserialize(value)
+    }
+}
+
+/// A single wire value. Inside a repeated field a length prefixed value may hold packed numbers
+struct WireValue<'a, 'de> {
+    field: FieldView<'de>,
+    repeated: Option<&'a mut RepeatedAccess<'de>>,
+    options: DeserializeOptions,
+}
+
+impl<'a, 'de> WireValue<'a, 'de> {
+    /// Start reading packed numbers if the value is length prefixed and inside a repeated field.
+    /// Empty packed values hold no numbers and are skipped. If no values are left the repeated field is finished and an error is returned
+    fn into_packed(self) -> Result<Result<Packed<'a, 'de>, Self>, SunlightError> {
+        let Some(repeated) = self.repeated else {
+            return Ok(Err(self));
+        };
+        let mut field = self.field;
+        loop {
+            match field.value {
+                RawValue::Len([]) => {
+                    let Some(next) = repeated.values.next() else {
+                        repeated.finished = true;
+                        return Err(SunlightError::Deserialize);
+                    };
+                    field = next;
+                }
+                RawValue::Len(payload) => {
+                    repeated.packed = payload;
+                    return Ok(Ok(Packed {
+                        data: &mut repeated.packed,
+                        options: self.options,
+                    }));
+                }
+                _ => {
+                    return Ok(Err(WireValue {
+                        field,
+                        repeated: Some(repeated),
+                        options: self.options,
+                    }));
+                }
+            }
+        }
+    }
+
+    fn signed(&self) -> Result<i64, SunlightError> {
+        let number = match self.field.value {
+            RawValue::VarInt(number) if self.options.zigzag => Some(zigzag(number)),
+            _ => self.field.as_i64(),
+        };
+        number.ok_or_else(|| mismatch(&self.field, "integer"))
+    }
+
+    fn unsigned(&self) -> Result<u64, SunlightError> {
+        self.field
+            .as_u64()
+            .ok_or_else(|| mismatch(&self.field, "integer"))
+    }
+
+    fn bytes(&self) -> Result<&'de [u8], SunlightError> {
+        self.field
+            .as_bytes()
+            .ok_or_else(|| mismatch(&self.field, "bytes"))
+    }
+
+    fn message(&self) -> Result<Deserializer<'de>, SunlightError> {
+        Ok(Deserializer::with_options(self.bytes()?, self.options))
+    }
+}
+
+/// Deserialize a number from the value or from the start of a packed value
+macro_rules! number {
+    ($($method:ident => $visit:ident($read:ident),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.into_packed()? {
+                    Ok(packed) => packed.$method(visitor),
+                    Err(value) => visitor.$visit(value.$read()?),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for WireValue<'_, 'de> {
+    type Error = SunlightError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.field.value {
+            RawValue::VarInt(number) | RawValue::Fixed64(number) => visitor.visit_u64(number),
+            RawValue::Fixed32(number) => visitor.visit_u32(number),
+            RawValue::Len(payload) => match std::str::from_utf8(payload) {
+                Ok(text) => visitor.visit_borrowed_str(text),
+                Err(_) => visitor.visit_borrowed_bytes(payload),
+            },
+            RawValue::Remaining(remaining) => visitor.visit_borrowed_bytes(remaining),
+        }
+    }
+
+    number! {
+        deserialize_i8 => visit_i64(signed),
+        deserialize_i16 => visit_i64(signed),
+        deserialize_i32 => visit_i64(signed),
+        deserialize_i64 => visit_i64(signed),
+        deserialize_u8 => visit_u64(unsigned),
+        deserialize_u16 => visit_u64(unsigned),
+        deserialize_u32 => visit_u64(unsigned),
+        deserialize_u64 => visit_u64(unsigned),
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.into_packed()? {
+            Ok(packed) => packed.deserialize_bool(visitor),
+            Err(value) => visitor.visit_bool(value.unsigned()? != 0),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.into_packed()? {
+            Ok(packed) => packed.deserialize_f32(visitor),
+            Err(value) => match value.field.value {
+                RawValue::Fixed32(bits) => visitor.visit_f32(f32::from_bits(bits)),
+                RawValue::Fixed64(bits) => visitor.visit_f64(f64::from_bits(bits)),
+                _ => Err(mismatch(&value.field, "float")),
+            },
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.into_packed()? {
+            Ok(packed) => packed.deserialize_f64(visitor),
+            Err(value) => match value.field.as_f64() {
+                Some(number) => visitor.visit_f64(number),
+                None => Err(mismatch(&value.field, "double")),
+            },
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let payload = self.bytes()?;
+        match std::str::from_utf8(payload) {
+            Ok(text) => visitor.visit_borrowed_str(text),
+            Err(_) => Err(mismatch(&self.field, "string")),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_bytes(self.bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Sub-messages are read as a map of field number to values
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.message()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.message()?.deserialize_any(visitor)
+    }
+
+    /// Protobuf enums are numbers. The number is used as the variant index
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let number = self.unsigned()?;
+        let index = u32::try_from(number).map_err(|_err| mismatch(&self.field, "enum"))?;
+        visitor.visit_enum(U32Deserializer::<SunlightError>::new(index))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char unit_struct seq tuple tuple_struct identifier
+    }
+}
+
+/// Packed numbers in a repeated field. Each call reads one number
+struct Packed<'a, 'de> {
+    data: &'a mut &'de [u8],
+    options: DeserializeOptions,
+}
+
+impl Packed<'_, '_> {
+    fn varint(&mut self) -> Result<u64, SunlightError> {
+        let (input, number) = parse_varint(self.data).map_err(packed_error)?;
+        *self.data = input;
+        Ok(number)
+    }
+
+    fn signed(&mut self) -> Result<i64, SunlightError> {
+        let number = self.varint()?;
+        if self.options.zigzag {
+            return Ok(zigzag(number));
+        }
+        Ok(number as i64)
+    }
+
+    fn unsigned(&mut self) -> Result<u64, SunlightError> {
+        self.varint()
+    }
+}
+
+/// Read a varint from the packed value
+macro_rules! packed_varint {
+    ($($method:ident => $visit:ident($read:ident),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.$read()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Packed<'_, 'de> {
+    type Error = SunlightError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_u64(visitor)
+    }
+
+    packed_varint! {
+        deserialize_i8 => visit_i64(signed),
+        deserialize_i16 => visit_i64(signed),
+        deserialize_i32 => visit_i64(signed),
+        deserialize_i64 => visit_i64(signed),
+        deserialize_u8 => visit_u64(unsigned),
+        deserialize_u16 => visit_u64(unsigned),
+        deserialize_u32 => visit_u64(unsigned),
+        deserialize_u64 => visit_u64(unsigned),
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_bool(self.varint()? != 0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let (input, bits) = parse_fixed32(self.data).map_err(packed_error)?;
+        *self.data = input;
+        visitor.visit_f32(f32::from_bits(bits))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let (input, bits) = parse_fixed64(self.data).map_err(packed_error)?;
+        *self.data = input;
+        visitor.visit_f64(f64::from_bits(bits))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let index = u32::try_from(self.varint()?).map_err(|_err| SunlightError::Deserialize)?;
+        visitor.visit_enum(U32Deserializer::<SunlightError>::new(index))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn packed_error(err: nom::Err<nom::error::Error<&[u8]>>) -> SunlightError {
+    error!("[sunlight] could not read packed value: {err:?}");
+    SunlightError::Deserialize
+}
+
+fn mismatch(field: &FieldView<'_>, expected: &str) -> SunlightError {
+    error!(
+        "[sunlight] field {} at offset {} has wire type {:?}, expected {expected}",
+        field.field(),
+        field.offset(),
+        field.wire_type()
+    );
+    SunlightError::Deserialize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeserializeOptions, from_bytes, from_bytes_options};
+    use crate::encode::text::text_to_protobuf;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Name {
+        #[serde(rename = "1")]
+        first: String,
+        #[serde(rename = "2", default)]
+        last: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        Unknown,
+        Person,
+        Group,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person<'a> {
+        #[serde(rename = "1")]
+        id: i32,
+        #[serde(rename = "2")]
+        name: Name,
+        #[serde(rename = "3", default)]
+        scores: Vec<u32>,
+        #[serde(rename = "4")]
+        weight: f64,
+        #[serde(rename = "5", borrow)]
+        avatar: &'a [u8],
+        #[serde(rename = "6")]
+        kind: Kind,
+        #[serde(rename = "7", default)]
+        aliases: Vec<String>,
+        #[serde(rename = "8", default)]
+        labels: HashMap<String, u64>,
+        #[serde(rename = "9")]
+        missing: Option<u64>,
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let data = text_to_protobuf(
+            r#"
+            1: -2
+            2 { 1: "Jane" 3: 99 }
+            3: "\001\002"
+            3: 300
+            4: 0x4059000000000000
+            5: "\377\000"
+            6: 2
+            7: "a"
+            7: "b"
+            8 { 1: "x" 2: 1 }
+            8 { 1: "y" }
+            10: 7
+            "#,
+        )
+        .unwrap();
+        let person: Person<'_> = from_bytes(&data).unwrap();
+        assert_eq!(person.id, -2);
+        assert_eq!(
+            person.name,
+            Name {
+                first: String::from("Jane"),
+                last: String::new()
+            }
+        );
+        assert_eq!(person.scores, [1, 2, 300]);
+        assert_eq!(person.weight, 100.0);
+        assert_eq!(person.avatar, [255, 0]);
+        assert_eq!(person.kind, Kind::Group);
+        assert_eq!(person.aliases, ["a", "b"]);
+        assert_eq!(person.labels["x"], 1);
+        assert_eq!(person.labels["y"], 0);
+        assert_eq!(person.missing, None);
+    }
+
+    #[test]
+    fn test_from_bytes_zigzag() {
+        #[derive(Deserialize)]
+        struct Signed {
+            #[serde(rename = "1")]
+            value: i64,
+            #[serde(rename = "2")]
+            packed: Vec<i32>,
+        }
+
+        let data = [8, 3, 18, 2, 1, 4];
+        let options = DeserializeOptions { zigzag: true };
+        let result: Signed = from_bytes_options(&data, options).unwrap();
+        assert_eq!(result.value, -2);
+        assert_eq!(result.packed, [-1, 2]);
+    }
+
+    #[test]
+    fn test_from_bytes_empty_packed() {
+        #[derive(Deserialize)]
+        struct Repeated {
+            #[serde(rename = "1")]
+            numbers: Vec<u32>,
+            #[serde(rename = "2")]
+            names: Vec<String>,
+        }
+
+        // Empty packed values hold no numbers but an empty string is still a value
+        let data = [10, 0, 18, 0];
+        let result: Repeated = from_bytes(&data).unwrap();
+        assert!(result.numbers.is_empty());
+        assert_eq!(result.names, [""]);
+
+        let data = [10, 0, 8, 1, 10, 0, 10, 1, 2, 10, 0, 18, 0];
+        let result: Repeated = from_bytes(&data).unwrap();
+        assert_eq!(result.numbers, [1, 2]);
+    }
+
+    #[test]
+    fn test_from_bytes_errors() {
+        #[derive(Debug, Deserialize)]
+        struct Wrong {
+            #[serde(rename = "1")]
+            _value: String,
+        }
+
+        // Field 1 is a varint
+        assert!(from_bytes::<Wrong>(&[8, 1]).is_err());
+        // Field 1 is missing a byte
+        assert!(from_bytes::<Wrong>(&[10, 2, 97]).is_err());
+        // Field 1 is required
+        assert!(from_bytes::<Wrong>(&[]).is_err());
+    }
+}
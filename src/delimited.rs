@@ -0,0 +1,96 @@
+use crate::{error::SunlightError, tags::length::parse_length_payload};
+use log::error;
+
+/// A message in a stream of length delimited messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DelimitedRecord<'a> {
+    /**Offset to the length prefix from the start of the stream */
+    pub offset: usize,
+    /**Message bytes without the length prefix */
+    pub data: &'a [u8],
+}
+
+/// Iterate through a stream of messages that are each prefixed with a varint length, as written by `writeDelimitedTo`
+///
+/// # Example
+/// ```rust
+/// let stream = [2, 8, 1, 3, 18, 1, 97];
+/// let records: Vec<_> = sunlight::delimited::iter_delimited(&stream)
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[1].offset, 3);
+/// assert_eq!(records[1].data, [18, 1, 97]);
+/// ```
+pub fn iter_delimited(data: &[u8]) -> Delimited<'_> {
+    Delimited {
+        data,
+        offset: 0,
+        failed: false,
+    }
+}
+
+/// Iterator over length delimited messages. Iteration stops after the first error
+#[derive(Debug, Clone)]
+pub struct Delimited<'a> {
+    data: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl Delimited<'_> {
+    /// Offset to the next record from the start of the stream. After an error this is the offset of the record that could not be read
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for Delimited<'a> {
+    type Item = Result<DelimitedRecord<'a>, SunlightError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.failed {
+            return None;
+        }
+
+        match parse_length_payload(self.data) {
+            Ok((input, payload)) => {
+                let record = DelimitedRecord {
+                    offset: self.offset,
+                    data: payload,
+                };
+                self.offset += self.data.len() - input.len();
+                self.data = input;
+                Some(Ok(record))
+            }
+            Err(err) => {
+                error!(
+                    "[sunlight] could not read delimited message at offset {}: {err:?}",
+                    self.offset
+                );
+                self.failed = true;
+                Some(Err(SunlightError::Parser))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::iter_delimited;
+
+    #[test]
+    fn test_iter_delimited() {
+        // The last message is missing a byte
+        let test = [0, 2, 8, 1, 3, 18, 1];
+        let records: Vec<_> = iter_delimited(&test).collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].as_ref().unwrap().data.is_empty());
+        assert_eq!(records[1].as_ref().unwrap().offset, 1);
+        assert!(records[2].is_err());
+
+        let mut stream = iter_delimited(&test);
+        assert_eq!(stream.nth(1).unwrap().unwrap().offset, 1);
+        assert_eq!(stream.offset(), 4);
+    }
+}
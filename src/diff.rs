@@ -0,0 +1,379 @@
+use crate::{
+    encode::edit::join_step,
+    light::{ProtoTag, ProtoValue},
+    render::json::{JsonOptions, value_to_json},
+};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A difference between two messages
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /**Path to the value, such as `2.1[1].5`. Repeated values use the index in the new message, unless removed */
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old: Option<ProtoValue>,
+    pub new: Option<ProtoValue>,
+    /**Offset to the field tag in the old data */
+    pub old_offset: Option<usize>,
+    /**Offset to the field tag in the new data */
+    pub new_offset: Option<usize>,
+}
+
+/// A single value of a field and where it was found
+struct Entry<'a> {
+    value: &'a ProtoValue,
+    offset: Option<usize>,
+}
+
+/// Compare two decoded messages field by field. Sub-messages are compared recursively and values of repeated fields are aligned so an inserted value does not change every value after it
+///
+/// # Example
+/// ```rust
+/// use sunlight::diff::{ChangeKind, diff_protobuf};
+///
+/// // Field 2 is a sub-message
+/// let old = sunlight::light::extract_protobuf(&[8, 1, 18, 3, 8, 150, 1]).unwrap();
+/// let new = sunlight::light::extract_protobuf(&[8, 1, 18, 3, 8, 151, 1, 24, 1]).unwrap();
+/// let changes = diff_protobuf(&old, &new);
+/// assert_eq!(changes[0].path, "2.1");
+/// assert_eq!(changes[0].kind, ChangeKind::Changed);
+/// assert_eq!(changes[1].path, "3");
+/// assert_eq!(changes[1].kind, ChangeKind::Added);
+/// ```
+pub fn diff_protobuf(
+    old: &HashMap<usize, ProtoTag>,
+    new: &HashMap<usize, ProtoTag>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_message(old, new, "", &mut changes);
+    changes
+}
+
+/// Render the changes as one line per change. Added values start with `+`, removed values with `-` and changed values with `~`
+pub fn render_changes(changes: &[Change]) -> String {
+    let options = JsonOptions::default();
+    let mut output = String::new();
+    for change in changes {
+        let old = change
+            .old
+            .as_ref()
+            .map(|value| value_to_json(value, &options).to_string());
+        let new = change
+            .new
+            .as_ref()
+            .map(|value| value_to_json(value, &options).to_string());
+        let _ = match (change.kind, old, new) {
+            (ChangeKind::Added, _, Some(new)) => writeln!(output, "+ {}: {new}", change.path),
+            (ChangeKind::Removed, Some(old), _) => writeln!(output, "- {}: {old}", change.path),
+            (_, old, new) => writeln!(
+                output,
+                "~ {}: {} -> {}",
+                change.path,
+                old.unwrap_or_default(),
+                new.unwrap_or_default()
+            ),
+        };
+    }
+    output
+}
+
+/// Compare the fields of two messages
+fn diff_message(
+    old: &HashMap<usize, ProtoTag>,
+    new: &HashMap<usize, ProtoTag>,
+    parent: &str,
+    changes: &mut Vec<Change>,
+) {
+    let mut fields: Vec<usize> = old.keys().chain(new.keys()).copied().collect();
+    fields.sort_unstable();
+    fields.dedup();
+
+    for field in fields {
+        let old_values = entries(old.get(&field));
+        let new_values = entries(new.get(&field));
+        let repeated = old_values.len() > 1 || new_values.len() > 1;
+        let path = |index: usize| join_step(parent, field, repeated.then_some(index));
+
+        let pairs = align(&old_values, &new_values);
+        let (mut old_index, mut new_index) = (0, 0);
+        // A final pair past the end of both lists handles trailing values
+        for (old_match, new_match) in pairs
+            .into_iter()
+            .chain([(old_values.len(), new_values.len())])
+        {
+            // Values between matches are paired up as changes. Any left over were added or removed
+            while old_index < old_match && new_index < new_match {
+                diff_value(
+                    &old_values[old_index],
+                    &new_values[new_index],
+                    &path(new_index),
+                    changes,
+                );
+                old_index += 1;
+                new_index += 1;
+            }
+            while old_index < old_match {
+                let entry = &old_values[old_index];
+                changes.push(Change {
+                    path: path(old_index),
+                    kind: ChangeKind::Removed,
+                    old: Some(entry.value.clone()),
+                    new: None,
+                    old_offset: entry.offset,
+                    new_offset: None,
+                });
+                old_index += 1;
+            }
+            while new_index < new_match {
+                let entry = &new_values[new_index];
+                changes.push(Change {
+                    path: path(new_index),
+                    kind: ChangeKind::Added,
+                    old: None,
+                    new: Some(entry.value.clone()),
+                    old_offset: None,
+                    new_offset: entry.offset,
+                });
+                new_index += 1;
+            }
+            // Skip the matching values
+            old_index += 1;
+            new_index += 1;
+        }
+    }
+}
+
+/// Compare two values at the same path
+fn diff_value(old: &Entry<'_>, new: &Entry<'_>, path: &str, changes: &mut Vec<Change>) {
+    if old.value == new.value {
+        return;
+    }
+    if let (ProtoValue::Message(old_message), ProtoValue::Message(new_message)) =
+        (old.value, new.value)
+    {
+        diff_message(old_message, new_message, path, changes);
+        return;
+    }
+    changes.push(Change {
+        path: path.to_string(),
+        kind: ChangeKind::Changed,
+        old: Some(old.value.clone()),
+        new: Some(new.value.clone()),
+        old_offset: old.offset,
+        new_offset: new.offset,
+    });
+}
+
+/// Split a field into its values
+fn entries(proto_tag: Option<&ProtoTag>) -> Vec<Entry<'_>> {
+    let Some(proto_tag) = proto_tag else {
+        return Vec::new();
+    };
+    proto_tag
+        .value
+        .values()
+        .iter()
+        .enumerate()
+        .map(|(index, value)| Entry {
+            value,
+            offset: proto_tag.records.get(index).map(|record| record.offset),
+        })
+        .collect()
+}
+
+/// Largest number of value comparisons used to align a repeated field. Larger fields are compared by index
+const MAX_ALIGN_WORK: usize = 1 << 26;
+
+/// Find the longest list of equal values that appear in the same order in both lists. Returns the index pairs of those values
+fn align(old: &[Entry<'_>], new: &[Entry<'_>]) -> Vec<(usize, usize)> {
+    // Values that did not change at the start and end are common and cheap to match
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_entry, new_entry)| old_entry.value == new_entry.value)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old_entry, new_entry)| old_entry.value == new_entry.value)
+        .count();
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|index| (index, index)).collect();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    if old_middle.len().saturating_mul(new_middle.len()) <= MAX_ALIGN_WORK {
+        hirschberg(old_middle, new_middle, (prefix, prefix), &mut pairs);
+    }
+    pairs.extend((0..suffix).map(|index| (old.len() - suffix + index, new.len() - suffix + index)));
+    pairs
+}
+
+/// Align two lists using memory linear to their length. Start is the position of the lists in the full lists
+fn hirschberg(
+    old: &[Entry<'_>],
+    new: &[Entry<'_>],
+    start: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    if old.len() == 1 {
+        if let Some(index) = new.iter().position(|entry| entry.value == old[0].value) {
+            pairs.push((start.0, start.1 + index));
+        }
+        return;
+    }
+
+    // Split the new list where the two halves of the old list share the most values
+    let middle = old.len() / 2;
+    let forward = lcs_lengths(old[..middle].iter(), new.iter());
+    let backward = lcs_lengths(old[middle..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len())
+        .max_by_key(|index| {
+            (
+                forward[*index] + backward[new.len() - index],
+                usize::MAX - index,
+            )
+        })
+        .unwrap_or_default();
+
+    hirschberg(&old[..middle], &new[..split], start, pairs);
+    hirschberg(
+        &old[middle..],
+        &new[split..],
+        (start.0 + middle, start.1 + split),
+        pairs,
+    );
+}
+
+/// Length of the longest common subsequence of the old list and every prefix of the new list
+fn lcs_lengths<'a, 'b: 'a>(
+    old: impl Iterator<Item = &'a Entry<'b>>,
+    new: impl Iterator<Item = &'a Entry<'b>> + Clone,
+) -> Vec<usize> {
+    let mut previous = vec![0; new.clone().count() + 1];
+    let mut current = previous.clone();
+    for old_entry in old {
+        for (index, new_entry) in new.clone().enumerate() {
+            current[index + 1] = if old_entry.value == new_entry.value {
+                previous[index] + 1
+            } else {
+                previous[index + 1].max(current[index])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeKind, Entry, align, diff_protobuf, render_changes};
+    use crate::light::{ProtoValue, extract_protobuf};
+
+    #[test]
+    fn test_diff_protobuf() {
+        // Field 2 changes from 1 to 2, field 3 is removed and field 4 is added
+        let old = extract_protobuf(&[8, 1, 16, 1, 24, 5]).unwrap();
+        let new = extract_protobuf(&[8, 1, 16, 2, 32, 7]).unwrap();
+        let changes = diff_protobuf(&old, &new);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].path, "2");
+        assert_eq!(changes[0].kind, ChangeKind::Changed);
+        assert_eq!(changes[0].old, Some(ProtoValue::VarInt(1)));
+        assert_eq!(changes[0].new_offset, Some(2));
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+        assert_eq!(changes[2].kind, ChangeKind::Added);
+        assert_eq!(changes[2].new, Some(ProtoValue::VarInt(7)));
+
+        assert_eq!(render_changes(&changes), "~ 2: 1 -> 2\n- 3: 5\n+ 4: 7\n");
+        assert!(diff_protobuf(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_protobuf_repeated() {
+        // A value is inserted at the start of field 1 and the last value of field 1 changes
+        let old = extract_protobuf(&[8, 2, 8, 3, 8, 4]).unwrap();
+        let new = extract_protobuf(&[8, 1, 8, 2, 8, 3, 8, 5]).unwrap();
+        let changes = diff_protobuf(&old, &new);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "1[0]");
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+        assert_eq!(changes[1].path, "1[3]");
+        assert_eq!(changes[1].kind, ChangeKind::Changed);
+        assert_eq!(changes[1].old_offset, Some(4));
+    }
+
+    fn entries(values: &[ProtoValue]) -> Vec<Entry<'_>> {
+        values
+            .iter()
+            .map(|value| Entry {
+                value,
+                offset: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_align() {
+        let list = |numbers: &[u64]| -> Vec<ProtoValue> {
+            numbers
+                .iter()
+                .map(|number| ProtoValue::VarInt(*number))
+                .collect()
+        };
+        let old = list(&[0, 1, 2, 3, 4, 5, 0]);
+        let new = list(&[0, 2, 6, 4, 3, 5, 7, 0]);
+        let pairs = align(&entries(&old), &entries(&new));
+
+        // Longest list of values in the same order is 0, 2, 3 or 4, 5, 0
+        assert_eq!(pairs.len(), 5);
+        assert!(
+            pairs
+                .windows(2)
+                .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1)
+        );
+        assert!(
+            pairs
+                .iter()
+                .all(|(old_index, new_index)| old[*old_index] == new[*new_index])
+        );
+
+        assert!(align(&entries(&list(&[1, 2])), &entries(&list(&[3]))).is_empty());
+        assert!(align(&[], &entries(&list(&[1]))).is_empty());
+    }
+
+    #[test]
+    fn test_align_large() {
+        let old: Vec<ProtoValue> = (0..10_000).map(ProtoValue::VarInt).collect();
+        assert_eq!(align(&entries(&old), &entries(&old)).len(), 10_000);
+
+        // Too large to align value by value. Only the unchanged start is matched
+        let new: Vec<ProtoValue> = (0..10_002)
+            .map(|number| ProtoValue::VarInt(number * 2))
+            .collect();
+        assert_eq!(align(&entries(&old), &entries(&new)), [(0, 0)]);
+    }
+
+    #[test]
+    fn test_diff_protobuf_nested() {
+        // Field 2 is a sub-message that gains field 2
+        let old = extract_protobuf(&[18, 3, 8, 150, 1, 24, 1]).unwrap();
+        let new = extract_protobuf(&[18, 5, 8, 150, 1, 16, 2]).unwrap();
+        let changes = diff_protobuf(&old, &new);
+
+        assert_eq!(render_changes(&changes), "+ 2.2: 2\n- 3: 1\n");
+    }
+}
@@ -0,0 +1,452 @@
+use crate::{
+    encode::{message::encode_length_value, wire::encode_tag},
+    error::SunlightError,
+    light::{
+        ExtractOptions, ProtoTag, ProtoValue, Tag, WireRecord, WireType, extract_protobuf_options,
+    },
+};
+use log::error;
+use std::collections::{HashMap, hash_map::Entry};
+
+/// Path to a field value. Each step selects a field number and which of its values to use
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPath {
+    pub steps: Vec<PathStep>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathStep {
+    pub field: usize,
+    /**Which value of a repeated field to use. Non-repeated fields use 0 */
+    pub index: usize,
+}
+
+enum Edit {
+    Set(ProtoValue),
+    Insert(ProtoValue),
+    /**Keep a field with one value left as `ProtoValue::Repeated` */
+    Delete {
+        keep_repeated: bool,
+    },
+}
+
+impl FieldPath {
+    /// Parse a path such as `2.1[1].5`. Steps are separated by `.` and an optional `[index]` selects a repeated value
+    pub fn parse(path: &str) -> Result<FieldPath, SunlightError> {
+        let mut steps = Vec::new();
+        for step in path.split('.') {
+            let (field, index) = split_step(step).ok_or_else(|| path_error(path))?;
+            match (field.parse(), index.unwrap_or("0").parse()) {
+                (Ok(field), Ok(index)) => steps.push(PathStep { field, index }),
+                _ => return Err(path_error(path)),
+            }
+        }
+        Ok(FieldPath { steps })
+    }
+}
+
+/// Split a step such as `5` or `1[2]` into its field and index. Shared with `query::Query` so both use the same path syntax
+pub(crate) fn split_step(step: &str) -> Option<(&str, Option<&str>)> {
+    match step.split_once('[') {
+        Some((field, index)) => index
+            .strip_suffix(']')
+            .map(|result| (field.trim(), Some(result.trim()))),
+        None => Some((step.trim(), None)),
+    }
+}
+
+/// Add a step to the end of a path. The index is only written for repeated fields
+pub(crate) fn join_step(parent: &str, field: usize, index: Option<usize>) -> String {
+    let step = match index {
+        Some(value) => format!("{field}[{value}]"),
+        None => field.to_string(),
+    };
+    if parent.is_empty() {
+        step
+    } else {
+        format!("{parent}.{step}")
+    }
+}
+
+/// Replace the value at the provided path. The length prefixes of all enclosing sub-messages are updated when encoding.
+/// The data must be decoded with `ExtractOptions::lossless` so untouched bytes are kept exactly as they were. Returns an error otherwise
+///
+/// # Example
+/// ```rust
+/// use sunlight::{
+///     encode::{edit::{FieldPath, set_value}, message::encode_protobuf},
+///     light::{ExtractOptions, ProtoValue, extract_protobuf_options},
+/// };
+///
+/// // Field 2 is a sub-message containing a string
+/// let proto_bytes = [8, 1, 18, 5, 10, 3, 97, 98, 99];
+/// let options = ExtractOptions {
+///     lossless: true,
+///     ..Default::default()
+/// };
+/// let mut proto_map = extract_protobuf_options(&proto_bytes, &options).unwrap();
+/// let path = FieldPath::parse("2.1").unwrap();
+/// set_value(&mut proto_map, &path, ProtoValue::String(String::from("abcd"))).unwrap();
+/// assert_eq!(encode_protobuf(&proto_map).unwrap(), [8, 1, 18, 6, 10, 4, 97, 98, 99, 100]);
+/// ```
+pub fn set_value(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    path: &FieldPath,
+    value: ProtoValue,
+) -> Result<(), SunlightError> {
+    edit_message(proto_map, &path.steps, Edit::Set(value))
+}
+
+/// Insert a new value at the provided path. The index of the last step is where the value is placed among any existing values of the field
+pub fn insert_value(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    path: &FieldPath,
+    value: ProtoValue,
+) -> Result<(), SunlightError> {
+    edit_message(proto_map, &path.steps, Edit::Insert(value))
+}
+
+/// Delete the value at the provided path. The field is removed once it has no values left.
+/// A repeated field with one value left becomes a single value, use `delete_value_options` if the data was decoded with `ExtractOptions::arrays`
+pub fn delete_value(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    path: &FieldPath,
+) -> Result<(), SunlightError> {
+    delete_value_options(proto_map, path, &ExtractOptions::default())
+}
+
+/// Delete the value at the provided path. Options should be the same ones used to decode the data, so fields that `ExtractOptions::arrays` says are repeated stay repeated
+pub fn delete_value_options(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    path: &FieldPath,
+    options: &ExtractOptions,
+) -> Result<(), SunlightError> {
+    let Some((last, parents)) = path.steps.split_last() else {
+        return Err(SunlightError::Path);
+    };
+    let parents: Vec<usize> = parents.iter().map(|step| step.field).collect();
+    let keep_repeated = options.arrays.is_repeated(&parents, last.field);
+    edit_message(proto_map, &path.steps, Edit::Delete { keep_repeated })
+}
+
+/// Walk the path and apply the edit to the last step
+fn edit_message(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    steps: &[PathStep],
+    edit: Edit,
+) -> Result<(), SunlightError> {
+    let (step, remaining) = match steps.split_first() {
+        Some(result) => result,
+        None => return Err(SunlightError::Path),
+    };
+    check_lossless(proto_map)?;
+    if remaining.is_empty() {
+        return apply_edit(proto_map, step, edit);
+    }
+
+    let proto_tag = proto_map.get_mut(&step.field).ok_or(SunlightError::Path)?;
+    let current = occurrence(&mut proto_tag.value, step.index).ok_or(SunlightError::Path)?;
+    let record = proto_tag.records.get_mut(step.index);
+    if current.wire_type() != WireType::Len {
+        return Err(SunlightError::Path);
+    }
+
+    // Strings and bytes may also be sub-messages
+    if !matches!(current, ProtoValue::Message(_)) {
+        let payload = match record.as_ref().and_then(|entry| entry.payload.as_ref()) {
+            Some(result) => result.clone(),
+            None => encode_length_value(current).ok_or(SunlightError::Encoder)?,
+        };
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        *current = ProtoValue::Message(extract_protobuf_options(&payload, &options)?);
+    }
+    let sub = match current {
+        ProtoValue::Message(result) => result,
+        _ => return Err(SunlightError::Path),
+    };
+    edit_message(sub, remaining, edit)?;
+
+    if let Some(entry) = record {
+        // The original bytes are out of date. Keep the offset so the field stays in place
+        entry.mark_edited();
+    }
+    Ok(())
+}
+
+/// Apply an edit to a field of the message
+fn apply_edit(
+    proto_map: &mut HashMap<usize, ProtoTag>,
+    step: &PathStep,
+    edit: Edit,
+) -> Result<(), SunlightError> {
+    match edit {
+        Edit::Set(value) => {
+            let proto_tag = proto_map.get_mut(&step.field).ok_or(SunlightError::Path)?;
+            let current =
+                occurrence(&mut proto_tag.value, step.index).ok_or(SunlightError::Path)?;
+            *current = value;
+            if let Some(record) = proto_tag.records.get_mut(step.index) {
+                // Keep the offset so the field stays in place
+                record.mark_edited();
+            }
+        }
+        Edit::Insert(value) => {
+            let proto_tag = match proto_map.entry(step.field) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut tag = Tag {
+                        tag_byte: 0,
+                        wire_type: value.wire_type(),
+                        field: step.field,
+                    };
+                    let mut tag_bytes = Vec::new();
+                    encode_tag(&tag, &mut tag_bytes);
+                    tag.tag_byte = tag_bytes[0];
+
+                    entry.insert(ProtoTag {
+                        tag,
+                        value,
+                        records: Vec::new(),
+                    });
+                    return Ok(());
+                }
+            };
+            if proto_tag.tag.wire_type != value.wire_type() {
+                error!(
+                    "[sunlight] cannot insert {:?} value into field {} with wire type {:?}",
+                    value.wire_type(),
+                    step.field,
+                    proto_tag.tag.wire_type
+                );
+                return Err(SunlightError::Path);
+            }
+
+            let values = match &mut proto_tag.value {
+                ProtoValue::Repeated(values) => values,
+                existing => {
+                    let first = std::mem::replace(existing, ProtoValue::Repeated(Vec::new()));
+                    *existing = ProtoValue::Repeated(vec![first]);
+                    match existing {
+                        ProtoValue::Repeated(values) => values,
+                        _ => return Err(SunlightError::Path),
+                    }
+                }
+            };
+            let index = step.index.min(values.len());
+            values.insert(index, value);
+
+            // Share the offset of the value it was placed before, otherwise it is written at the end
+            if let Some(offset) = proto_tag.records.get(index).map(|record| record.offset) {
+                proto_tag.records.insert(
+                    index,
+                    WireRecord {
+                        offset,
+                        payload: None,
+                        raw: None,
+                        edited: true,
+                    },
+                );
+            }
+        }
+        Edit::Delete { keep_repeated } => {
+            let proto_tag = proto_map.get_mut(&step.field).ok_or(SunlightError::Path)?;
+            match &mut proto_tag.value {
+                ProtoValue::Repeated(values) if step.index < values.len() => {
+                    values.remove(step.index);
+                    if values.is_empty() {
+                        proto_map.remove(&step.field);
+                        return Ok(());
+                    }
+                    if values.len() == 1 && !keep_repeated {
+                        proto_tag.value = values.remove(0);
+                    }
+                }
+                _ if step.index == 0 => {
+                    proto_map.remove(&step.field);
+                    return Ok(());
+                }
+                _ => return Err(SunlightError::Path),
+            }
+            if step.index < proto_tag.records.len() {
+                proto_tag.records.remove(step.index);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the untouched values of a message kept their exact bytes. Every message along the edited path is encoded again, so values without bytes would be rewritten
+fn check_lossless(proto_map: &HashMap<usize, ProtoTag>) -> Result<(), SunlightError> {
+    let missing = proto_map.values().find(|proto_tag| {
+        proto_tag
+            .records
+            .iter()
+            .any(|record| !record.edited && record.raw.is_none())
+    });
+    if let Some(proto_tag) = missing {
+        error!(
+            "[sunlight] field {} was not decoded in lossless mode. Editing would change its bytes",
+            proto_tag.tag.field
+        );
+        return Err(SunlightError::Encoder);
+    }
+    Ok(())
+}
+
+/// Get a value of a field. Repeated fields have one value per occurrence
+fn occurrence(value: &mut ProtoValue, index: usize) -> Option<&mut ProtoValue> {
+    match value {
+        ProtoValue::Repeated(values) => values.get_mut(index),
+        _ if index == 0 => Some(value),
+        _ => None,
+    }
+}
+
+fn path_error(path: &str) -> SunlightError {
+    error!("[sunlight] could not parse field path: {path}");
+    SunlightError::Path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldPath, PathStep, delete_value, delete_value_options, insert_value, set_value};
+    use crate::{
+        encode::message::encode_protobuf,
+        light::{
+            ArrayMode, ExtractOptions, ProtoValue, extract_protobuf, extract_protobuf_options,
+        },
+    };
+
+    #[test]
+    fn test_field_path_parse() {
+        let result = FieldPath::parse("2.1[3].5").unwrap();
+        assert_eq!(
+            result.steps,
+            vec![
+                PathStep { field: 2, index: 0 },
+                PathStep { field: 1, index: 3 },
+                PathStep { field: 5, index: 0 }
+            ]
+        );
+        assert!(FieldPath::parse("2.a").is_err());
+        assert!(FieldPath::parse("2[1").is_err());
+    }
+
+    #[test]
+    fn test_set_value_length_grows() {
+        // Field 1 uses a non-minimal varint. Field 2 is a sub-message containing a bundle ID
+        let test = [
+            8, 129, 0, 18, 23, 10, 21, 99, 111, 109, 46, 97, 112, 112, 108, 101, 46, 83, 97, 102,
+            97, 114, 105, 46, 116, 101, 115, 116, 24, 1,
+        ];
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let mut proto_map = extract_protobuf_options(&test, &options).unwrap();
+
+        let bundle = "com.apple.".repeat(13);
+        let path = FieldPath::parse("2.1").unwrap();
+        set_value(&mut proto_map, &path, ProtoValue::String(bundle.clone())).unwrap();
+
+        let result = encode_protobuf(&proto_map).unwrap();
+        // Untouched bytes are kept, both length prefixes now need two bytes
+        assert_eq!(result[..3], [8, 129, 0]);
+        assert_eq!(result[3..7], [18, 133, 1, 10]);
+        assert_eq!(result[7..9], [130, 1]);
+        assert_eq!(result[result.len() - 2..], [24, 1]);
+
+        let updated = extract_protobuf(&result).unwrap();
+        let sub = updated.get(&2).unwrap().value.as_message().unwrap();
+        assert_eq!(sub.get(&1).unwrap().value.as_str().unwrap(), bundle);
+    }
+
+    fn lossless() -> ExtractOptions {
+        ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_insert_value() {
+        let test = [8, 1, 18, 3, 8, 150, 1, 8, 3];
+        let mut proto_map = extract_protobuf_options(&test, &lossless()).unwrap();
+
+        let path = FieldPath::parse("1[1]").unwrap();
+        insert_value(&mut proto_map, &path, ProtoValue::VarInt(2)).unwrap();
+        let path = FieldPath::parse("2.2").unwrap();
+        insert_value(&mut proto_map, &path, ProtoValue::String(String::from("a"))).unwrap();
+
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [8, 1, 18, 6, 8, 150, 1, 18, 1, 97, 8, 2, 8, 3]);
+
+        let path = FieldPath::parse("1").unwrap();
+        assert!(
+            insert_value(&mut proto_map, &path, ProtoValue::String(String::from("a"))).is_err()
+        );
+    }
+
+    #[test]
+    fn test_delete_value() {
+        let test = [8, 1, 18, 3, 8, 150, 1, 8, 3, 24, 1];
+        let mut proto_map = extract_protobuf_options(&test, &lossless()).unwrap();
+
+        delete_value(&mut proto_map, &FieldPath::parse("1[0]").unwrap()).unwrap();
+        delete_value(&mut proto_map, &FieldPath::parse("3").unwrap()).unwrap();
+        assert_eq!(proto_map.get(&1).unwrap().value, ProtoValue::VarInt(3));
+        assert!(!proto_map.contains_key(&3));
+
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [18, 3, 8, 150, 1, 8, 3]);
+
+        assert!(delete_value(&mut proto_map, &FieldPath::parse("1[4]").unwrap()).is_err());
+
+        // A repeated field with a single value
+        let mut proto_map = extract_protobuf_options(&[8, 1, 16, 2], &lossless()).unwrap();
+        proto_map.get_mut(&1).unwrap().value = ProtoValue::Repeated(vec![ProtoValue::VarInt(1)]);
+        delete_value(&mut proto_map, &FieldPath::parse("1").unwrap()).unwrap();
+        assert!(!proto_map.contains_key(&1));
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), [16, 2]);
+    }
+
+    #[test]
+    fn test_delete_value_always_array() {
+        let options = ExtractOptions {
+            lossless: true,
+            arrays: ArrayMode::Always,
+        };
+        let path = FieldPath::parse("1").unwrap();
+        let mut proto_map = extract_protobuf_options(&[8, 1, 16, 2], &options).unwrap();
+        delete_value_options(&mut proto_map, &path, &options).unwrap();
+        assert!(!proto_map.contains_key(&1));
+
+        // Fields keep the same shape as other messages decoded with the same options
+        let mut proto_map = extract_protobuf_options(&[8, 1, 8, 3], &options).unwrap();
+        delete_value_options(&mut proto_map, &path, &options).unwrap();
+        assert_eq!(
+            proto_map[&1].value,
+            ProtoValue::Repeated(vec![ProtoValue::VarInt(3)])
+        );
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), [8, 3]);
+    }
+
+    #[test]
+    fn test_set_value_requires_lossless() {
+        // Field 1 has trailing NULL characters and field 3 uses a non-minimal varint
+        let test = [8, 1, 18, 3, 97, 0, 0, 24, 129, 0];
+        let path = FieldPath::parse("1").unwrap();
+        let mut proto_map = extract_protobuf(&test).unwrap();
+        assert!(set_value(&mut proto_map, &path, ProtoValue::VarInt(2)).is_err());
+
+        let mut proto_map = extract_protobuf_options(&test, &lossless()).unwrap();
+        set_value(&mut proto_map, &path, ProtoValue::VarInt(2)).unwrap();
+        assert_eq!(
+            encode_protobuf(&proto_map).unwrap(),
+            [8, 2, 18, 3, 97, 0, 0, 24, 129, 0]
+        );
+    }
+}
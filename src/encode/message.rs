@@ -0,0 +1,370 @@
+use crate::{
+    encode::wire::{encode_length, encode_tag, encode_varint},
+    error::SunlightError,
+    light::{ProtoTag, ProtoValue, Tag, WireRecord, WireType},
+    tags::var::parse_varint,
+};
+use log::error;
+use std::collections::HashMap;
+
+/// A single value of a field
+struct Occurrence<'a> {
+    tag: &'a Tag,
+    value: &'a ProtoValue,
+    record: Option<&'a WireRecord>,
+}
+
+/// Encode Protobuf data returned by `extract_protobuf` back into bytes.
+/// Values are written in the order they were decoded. Values without a `WireRecord` are written last, ordered by field number.
+/// Bytes kept by `ExtractOptions::lossless` are reused unless the record is marked with `WireRecord::mark_edited`.
+/// Without lossless mode the output is not byte exact. Non-minimal varints and length prefixes are written in their shortest form
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [10, 10, 112, 114, 111, 100, 117, 99, 116, 105, 111, 110, 16, 1];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// let data = sunlight::encode::message::encode_protobuf(&proto_map).unwrap();
+/// assert_eq!(data, proto_bytes);
+/// ```
+pub fn encode_protobuf(proto_map: &HashMap<usize, ProtoTag>) -> Result<Vec<u8>, SunlightError> {
+    let mut values: Vec<Occurrence<'_>> = proto_map.values().flat_map(occurrences).collect();
+    values.sort_by_key(|entry| {
+        (
+            entry.record.map_or(usize::MAX, |record| record.offset),
+            entry.tag.field,
+        )
+    });
+
+    let mut data = Vec::new();
+    for entry in values {
+        encode_value(entry.tag, entry.value, entry.record, &mut data)?;
+    }
+    Ok(data)
+}
+
+/// Encode a list of fields into Protobuf bytes. Fields are written in the order provided
+pub fn encode_fields(fields: &[ProtoTag]) -> Result<Vec<u8>, SunlightError> {
+    let mut data = Vec::new();
+    for entry in fields.iter().flat_map(occurrences) {
+        encode_value(entry.tag, entry.value, entry.record, &mut data)?;
+    }
+    Ok(data)
+}
+
+/// Split a field into its values. Repeated fields have one record per value
+fn occurrences(proto_tag: &ProtoTag) -> Vec<Occurrence<'_>> {
+    proto_tag
+        .value
+        .values()
+        .iter()
+        .enumerate()
+        .map(|(index, value)| Occurrence {
+            tag: &proto_tag.tag,
+            value,
+            record: proto_tag.records.get(index),
+        })
+        .collect()
+}
+
+/// Encode the tag and value of a single field value
+fn encode_value(
+    tag: &Tag,
+    value: &ProtoValue,
+    record: Option<&WireRecord>,
+    data: &mut Vec<u8>,
+) -> Result<(), SunlightError> {
+    // Lossless records keep the exact field bytes. Only reuse them if the value was not edited
+    let record = record.filter(|entry| !entry.edited);
+    if let Some(raw) = record.and_then(|entry| entry.raw.as_ref()) {
+        let Some(length) = header_length(raw).filter(|_| value.wire_type() == WireType::Len) else {
+            data.extend_from_slice(raw);
+            return Ok(());
+        };
+        // Sub-messages only keep their tag and length prefix. The payload is rebuilt from their fields
+        let payload = match record.and_then(|entry| entry.payload.as_ref()) {
+            Some(result) => result.clone(),
+            None => encode_length_value(value).ok_or_else(|| encode_error(tag, value))?,
+        };
+        if length == payload.len() {
+            data.extend_from_slice(raw);
+        } else {
+            // A field inside was changed without marking this record as edited
+            encode_tag(tag, data);
+            encode_varint(payload.len() as u64, data);
+        }
+        data.extend_from_slice(&payload);
+        return Ok(());
+    }
+
+    if let ProtoValue::Group(remaining) = value {
+        // The parser stops at these wire types and returns the remaining bytes
+        encode_tag(tag, data);
+        data.extend_from_slice(remaining);
+        return Ok(());
+    }
+
+    // A field may use different wire types across values
+    let wire_type = value.wire_type();
+    if wire_type == tag.wire_type {
+        encode_tag(tag, data);
+    } else {
+        let value_tag = Tag {
+            tag_byte: 0,
+            wire_type,
+            field: tag.field,
+        };
+        encode_tag(&value_tag, data);
+    }
+
+    match value {
+        ProtoValue::VarInt(number) => encode_varint(*number, data),
+        ProtoValue::Fixed64(bits) => data.extend_from_slice(&bits.to_le_bytes()),
+        ProtoValue::Fixed32(bits) => data.extend_from_slice(&bits.to_le_bytes()),
+        _ => {
+            if let Some(payload) = record.and_then(|entry| entry.payload.as_ref()) {
+                encode_length(payload, data);
+                return Ok(());
+            }
+            let payload = encode_length_value(value).ok_or_else(|| encode_error(tag, value))?;
+            encode_length(&payload, data);
+        }
+    }
+    Ok(())
+}
+
+/// Encode the payload of a length prefixed value
+pub(crate) fn encode_length_value(value: &ProtoValue) -> Option<Vec<u8>> {
+    match value {
+        ProtoValue::String(text) => Some(text.as_bytes().to_vec()),
+        ProtoValue::Bytes(bytes) => Some(bytes.clone()),
+        ProtoValue::Message(message) => encode_protobuf(message).ok(),
+        ProtoValue::Packed(values) => {
+            let mut payload = Vec::new();
+            for entry in values {
+                match entry {
+                    ProtoValue::VarInt(number) => encode_varint(*number, &mut payload),
+                    ProtoValue::Fixed64(bits) => payload.extend_from_slice(&bits.to_le_bytes()),
+                    ProtoValue::Fixed32(bits) => payload.extend_from_slice(&bits.to_le_bytes()),
+                    _ => return None,
+                }
+            }
+            Some(payload)
+        }
+        ProtoValue::VarInt(_)
+        | ProtoValue::Fixed32(_)
+        | ProtoValue::Fixed64(_)
+        | ProtoValue::Group(_)
+        | ProtoValue::Repeated(_) => None,
+    }
+}
+
+/// Get the length prefix if the bytes are only the tag and length prefix of a length prefixed value
+fn header_length(header: &[u8]) -> Option<usize> {
+    let len_wire = 2;
+    let (input, tag) = parse_varint(header).ok()?;
+    let (input, length) = parse_varint(input).ok()?;
+    if tag & 7 != len_wire || !input.is_empty() {
+        return None;
+    }
+    usize::try_from(length).ok()
+}
+
+fn encode_error(tag: &Tag, value: &ProtoValue) -> SunlightError {
+    error!(
+        "[sunlight] could not encode field {} with wire type {:?}: {value:?}",
+        tag.field, tag.wire_type
+    );
+    SunlightError::Encoder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_fields, encode_protobuf};
+    use crate::light::{
+        ExtractOptions, ProtoTag, ProtoValue, Tag, WireType, extract_protobuf,
+        extract_protobuf_options,
+    };
+    use std::{collections::HashMap, fs::read, path::PathBuf};
+
+    #[test]
+    fn test_encode_protobuf() {
+        let test = [
+            8, 1, 17, 0, 0, 0, 128, 76, 206, 217, 65, 25, 0, 0, 0, 32, 155, 208, 217, 65, 34, 55,
+            99, 111, 109, 46, 97, 112, 112, 108, 101, 46, 115, 105, 114, 105, 46, 109, 101, 116,
+            114, 105, 99, 115, 46, 77, 101, 116, 114, 105, 99, 115, 69, 120, 116, 101, 110, 115,
+            105, 111, 110, 46, 115, 99, 111, 114, 101, 99, 97, 114, 100, 46, 100, 97, 105, 108,
+            121, 42, 11, 78, 111, 116, 32, 83, 116, 97, 114, 116, 101, 100, 49, 134, 227, 69, 236,
+            1, 207, 217, 65, 56, 1, 64, 0, 72, 0, 81, 0, 0, 0, 192, 204, 255, 42, 64, 89, 0, 0, 0,
+            0, 0, 0, 240, 191, 97, 0, 0, 0, 192, 204, 255, 42, 64, 105, 0, 0, 0, 0, 0, 0, 240, 191,
+            113, 0, 0, 0, 0, 0, 0, 240, 191, 0, 0,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, test);
+    }
+
+    #[test]
+    fn test_encode_protobuf_blackboxprotobuf() {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data/blackboxprotobuf/test_message.out");
+        let data = read(test_path.to_str().unwrap()).unwrap();
+
+        let proto_map = extract_protobuf(&data).unwrap();
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn test_encode_protobuf_string_padding() {
+        // Trailing NULL characters are not part of the decoded string
+        let test = [10, 4, 97, 98, 0, 0];
+        let proto_map = extract_protobuf(&test).unwrap();
+        assert_eq!(proto_map[&1].value, ProtoValue::String(String::from("ab")));
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), test);
+    }
+
+    #[test]
+    fn test_encode_protobuf_edited() {
+        let test = [10, 3, 97, 98, 99, 16, 1];
+        let mut proto_map = extract_protobuf(&test).unwrap();
+        proto_map.get_mut(&1).unwrap().value = ProtoValue::String(String::from("abcd"));
+
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [10, 4, 97, 98, 99, 100, 16, 1]);
+    }
+
+    #[test]
+    fn test_encode_protobuf_lossless() {
+        // Non-minimal varints, an interleaved repeated field and a non-minimal length prefix
+        let test = [8, 129, 128, 0, 16, 2, 8, 3, 26, 131, 0, 97, 98, 99];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [8, 1, 16, 2, 8, 3, 26, 3, 97, 98, 99]);
+
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let proto_map = extract_protobuf_options(&test, &options).unwrap();
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, test);
+    }
+
+    #[test]
+    fn test_encode_protobuf_mark_edited() {
+        // The string has trailing NULL characters, so its payload is kept
+        let test = [10, 3, 97, 0, 0, 16, 1];
+        let mut proto_map = extract_protobuf(&test).unwrap();
+        let proto_tag = proto_map.get_mut(&1).unwrap();
+        proto_tag.value = ProtoValue::String(String::from("b"));
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), test);
+
+        proto_map.get_mut(&1).unwrap().records[0].mark_edited();
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [10, 1, 98, 16, 1]);
+    }
+
+    #[test]
+    fn test_encode_protobuf_lossless_edited() {
+        // Field 2 is a sub-message with a non-minimal varint
+        let test = [18, 4, 8, 129, 128, 0, 24, 1];
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let mut proto_map = extract_protobuf_options(&test, &options).unwrap();
+        let proto_tag = proto_map.get_mut(&3).unwrap();
+        proto_tag.value = ProtoValue::VarInt(2);
+        proto_tag.records[0].mark_edited();
+
+        let result = encode_protobuf(&proto_map).unwrap();
+        assert_eq!(result, [18, 4, 8, 129, 128, 0, 24, 2]);
+    }
+
+    #[test]
+    fn test_encode_protobuf_lossless_nested() {
+        // Sub-messages with non-minimal length prefixes three levels deep
+        let test = [10, 135, 0, 10, 132, 0, 8, 129, 128, 0, 16, 1];
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let mut proto_map = extract_protobuf_options(&test, &options).unwrap();
+        assert_eq!(proto_map[&1].records[0].raw, Some(vec![10, 135, 0]));
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), test);
+
+        // Only the inner record is marked, the outer length prefixes must still be updated
+        let ProtoValue::Message(outer) = &mut proto_map.get_mut(&1).unwrap().value else {
+            panic!("expected sub-message");
+        };
+        let ProtoValue::Message(inner) = &mut outer.get_mut(&1).unwrap().value else {
+            panic!("expected sub-message");
+        };
+        let proto_tag = inner.get_mut(&1).unwrap();
+        proto_tag.value = ProtoValue::VarInt(300);
+        proto_tag.records[0].mark_edited();
+        assert_eq!(
+            encode_protobuf(&proto_map).unwrap(),
+            [10, 5, 10, 3, 8, 172, 2, 16, 1]
+        );
+    }
+
+    #[test]
+    fn test_encode_fields() {
+        let nested = ProtoTag {
+            tag: Tag {
+                tag_byte: 8,
+                wire_type: WireType::VarInt,
+                field: 1,
+            },
+            value: ProtoValue::Repeated(vec![
+                ProtoValue::VarInt(u64::MAX),
+                ProtoValue::VarInt(300),
+            ]),
+            records: Vec::new(),
+        };
+        let fields = [
+            ProtoTag {
+                tag: Tag {
+                    tag_byte: 0,
+                    wire_type: WireType::Fixed64,
+                    field: 4,
+                },
+                value: ProtoValue::Fixed64(753770588.413478_f64.to_bits()),
+                records: Vec::new(),
+            },
+            ProtoTag {
+                tag: Tag {
+                    tag_byte: 0,
+                    wire_type: WireType::Len,
+                    field: 2,
+                },
+                value: ProtoValue::Message(HashMap::from([(1, nested)])),
+                records: Vec::new(),
+            },
+        ];
+        let result = encode_fields(&fields).unwrap();
+        assert_eq!(
+            result,
+            [
+                33, 217, 236, 52, 46, 208, 118, 198, 65, 18, 14, 8, 255, 255, 255, 255, 255, 255,
+                255, 255, 255, 1, 8, 172, 2
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Encoder")]
+    fn test_encode_fields_bad_value() {
+        let fields = [ProtoTag {
+            tag: Tag {
+                tag_byte: 0,
+                wire_type: WireType::Len,
+                field: 1,
+            },
+            value: ProtoValue::Packed(vec![ProtoValue::String(String::from("not a number"))]),
+            records: Vec::new(),
+        }];
+        let _ = encode_fields(&fields).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+pub mod edit;
+pub mod message;
+pub mod text;
+pub mod verify;
+pub(crate) mod wire;
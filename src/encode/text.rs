@@ -0,0 +1,495 @@
+use crate::{
+    encode::wire::{encode_length, encode_tag, encode_varint},
+    error::SunlightError,
+    light::{Tag, WireType},
+    schema::typedef::{FieldType, MessageDef},
+};
+use log::error;
+
+enum Token {
+    /**Field names, field numbers and scalar values */
+    Word(String),
+    /**Quoted string with escapes already decoded */
+    Text(Vec<u8>),
+    Symbol(char),
+}
+
+/// Sub-messages nested deeper than this are rejected instead of overflowing the stack. Same limit as the official Protobuf parsers
+const MAX_DEPTH: usize = 100;
+
+/// A value read from the text
+enum TextValue {
+    Word(String),
+    Text(Vec<u8>),
+    /**Encoded fields of a sub-message */
+    Message(Vec<u8>),
+}
+
+/// Parse Protobuf text format and encode it to Protobuf bytes. Fields must use numbers, like the output of `protoc --decode_raw`.
+/// Integers are varints, `0x` values with 8 or 16 hex digits are fixed 4 and 8 byte values, decimals are doubles (or floats with an `f` suffix) and quoted strings are length prefixed
+///
+/// # Example
+/// ```rust
+/// use sunlight::encode::text::text_to_protobuf;
+///
+/// let text = r#"
+///     1: 150
+///     2: "abc"
+///     3 { 1: 150 }
+///     5: 0x3fc00000
+/// "#;
+/// let data = text_to_protobuf(text).unwrap();
+/// assert_eq!(data, [8, 150, 1, 18, 3, 97, 98, 99, 26, 3, 8, 150, 1, 45, 0, 0, 192, 63]);
+/// ```
+pub fn text_to_protobuf(text: &str) -> Result<Vec<u8>, SunlightError> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+    parse_message(&tokens, &mut position, None, None, 0)
+}
+
+/// Parse Protobuf text format and encode it to Protobuf bytes. Fields may use names from the definition, values are encoded as the declared type
+pub fn text_to_protobuf_with_schema(
+    text: &str,
+    message: &MessageDef,
+) -> Result<Vec<u8>, SunlightError> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+    parse_message(&tokens, &mut position, Some(message), None, 0)
+}
+
+/// Parse fields until the closing symbol, or the end of the text for the top level message. Depth is the number of enclosing sub-messages
+fn parse_message(
+    tokens: &[Token],
+    position: &mut usize,
+    message: Option<&MessageDef>,
+    end: Option<char>,
+    depth: usize,
+) -> Result<Vec<u8>, SunlightError> {
+    let mut data = Vec::new();
+    loop {
+        let name = match tokens.get(*position) {
+            None if end.is_none() => return Ok(data),
+            None => return Err(text_error("missing closing bracket")),
+            Some(Token::Symbol(symbol)) if Some(*symbol) == end => {
+                *position += 1;
+                return Ok(data);
+            }
+            Some(Token::Symbol(',' | ';')) => {
+                *position += 1;
+                continue;
+            }
+            Some(Token::Word(name)) => name,
+            Some(_) => return Err(text_error("expected a field name")),
+        };
+        *position += 1;
+
+        let (field, field_type) = if let Ok(field) = name.parse::<usize>() {
+            let def = message.and_then(|value| value.fields.get(&field));
+            (field, def.map(|value| &value.field_type))
+        } else {
+            let def = message
+                .and_then(|value| value.fields.values().find(|entry| entry.name == *name))
+                .ok_or_else(|| text_error(&format!("unknown field {name}")))?;
+            (def.number, Some(&def.field_type))
+        };
+
+        if matches!(tokens.get(*position), Some(Token::Symbol(':'))) {
+            *position += 1;
+        }
+        // Repeated values may be written as a list
+        if matches!(tokens.get(*position), Some(Token::Symbol('['))) {
+            *position += 1;
+            loop {
+                match tokens.get(*position) {
+                    Some(Token::Symbol(']')) => {
+                        *position += 1;
+                        break;
+                    }
+                    Some(Token::Symbol(',')) => *position += 1,
+                    _ => {
+                        let value = parse_value(tokens, position, field_type, depth)?;
+                        encode_text_value(field, field_type, value, &mut data)?;
+                    }
+                }
+            }
+            continue;
+        }
+
+        let value = parse_value(tokens, position, field_type, depth)?;
+        encode_text_value(field, field_type, value, &mut data)?;
+    }
+}
+
+/// Read a single value. Adjacent strings are joined together
+fn parse_value(
+    tokens: &[Token],
+    position: &mut usize,
+    field_type: Option<&FieldType>,
+    depth: usize,
+) -> Result<TextValue, SunlightError> {
+    let token = tokens
+        .get(*position)
+        .ok_or_else(|| text_error("missing value"))?;
+    *position += 1;
+
+    let value = match token {
+        Token::Word(word) => TextValue::Word(word.clone()),
+        Token::Text(text) => {
+            let mut text = text.clone();
+            while let Some(Token::Text(next)) = tokens.get(*position) {
+                text.extend_from_slice(next);
+                *position += 1;
+            }
+            TextValue::Text(text)
+        }
+        Token::Symbol(open @ ('{' | '<')) => {
+            if depth >= MAX_DEPTH {
+                return Err(text_error("sub-messages are nested too deeply"));
+            }
+            let end = if *open == '{' { '}' } else { '>' };
+            let nested = match field_type {
+                Some(FieldType::Message(message)) => Some(message),
+                _ => None,
+            };
+            TextValue::Message(parse_message(
+                tokens,
+                position,
+                nested,
+                Some(end),
+                depth + 1,
+            )?)
+        }
+        Token::Symbol(symbol) => return Err(text_error(&format!("unexpected {symbol}"))),
+    };
+    Ok(value)
+}
+
+/// Encode the tag and value of a field
+fn encode_text_value(
+    field: usize,
+    field_type: Option<&FieldType>,
+    value: TextValue,
+    data: &mut Vec<u8>,
+) -> Result<(), SunlightError> {
+    let (wire_type, payload) = match value {
+        TextValue::Message(payload) | TextValue::Text(payload) => match field_type {
+            None
+            | Some(
+                FieldType::String
+                | FieldType::Bytes
+                | FieldType::Message(_)
+                | FieldType::WellKnown(_),
+            ) => (WireType::Len, payload),
+            Some(_) => return Err(text_error(&format!("field {field} is not length prefixed"))),
+        },
+        TextValue::Word(word) => match field_type {
+            Some(field_type) => typed_scalar(field_type, &word)?,
+            None => untyped_scalar(&word)?,
+        },
+    };
+
+    let tag = Tag {
+        tag_byte: 0,
+        wire_type,
+        field,
+    };
+    encode_tag(&tag, data);
+    if tag.wire_type == WireType::Len {
+        encode_length(&payload, data);
+    } else {
+        data.extend_from_slice(&payload);
+    }
+    Ok(())
+}
+
+/// Encode a scalar without a declared type
+fn untyped_scalar(word: &str) -> Result<(WireType, Vec<u8>), SunlightError> {
+    let digits = word.trim_start_matches('-');
+    if let Some(hex) = digits.strip_prefix("0x")
+        && matches!(hex.len(), 8 | 16)
+    {
+        let number = parse_integer(word).ok_or_else(|| text_error(word))?;
+        return Ok(match hex.len() {
+            8 => (WireType::Fixed32, (number as u32).to_le_bytes().to_vec()),
+            _ => (WireType::Fixed64, (number as u64).to_le_bytes().to_vec()),
+        });
+    }
+    if let Some(number) = parse_integer(word) {
+        return Ok(varint_bytes(number as u64));
+    }
+    if digits.starts_with("0x") || digits.bytes().all(|value| value.is_ascii_digit()) {
+        return Err(text_error(&format!("{word} is too large")));
+    }
+    match word {
+        "true" => return Ok(varint_bytes(1)),
+        "false" => return Ok(varint_bytes(0)),
+        _ => {}
+    }
+    if let Some(float) = word.strip_suffix('f').and_then(parse_float) {
+        return Ok((
+            WireType::Fixed32,
+            (float as f32).to_bits().to_le_bytes().to_vec(),
+        ));
+    }
+    let float = parse_float(word).ok_or_else(|| text_error(word))?;
+    Ok((WireType::Fixed64, float.to_bits().to_le_bytes().to_vec()))
+}
+
+/// Encode a scalar as the declared type
+fn typed_scalar(field_type: &FieldType, word: &str) -> Result<(WireType, Vec<u8>), SunlightError> {
+    let integer = || parse_integer(word).ok_or_else(|| text_error(word));
+    let float =
+        || parse_float(word.strip_suffix('f').unwrap_or(word)).ok_or_else(|| text_error(word));
+
+    let value = match field_type {
+        FieldType::Int64 | FieldType::Uint64 | FieldType::Int32 | FieldType::Uint32 => {
+            varint_bytes(integer()? as u64)
+        }
+        FieldType::Sint32 | FieldType::Sint64 => {
+            let number = integer()? as i64;
+            varint_bytes(((number << 1) ^ (number >> 63)) as u64)
+        }
+        FieldType::Bool => match word {
+            "true" => varint_bytes(1),
+            "false" => varint_bytes(0),
+            _ => varint_bytes(integer()? as u64),
+        },
+        FieldType::Double => (WireType::Fixed64, float()?.to_bits().to_le_bytes().to_vec()),
+        FieldType::Float => (
+            WireType::Fixed32,
+            (float()? as f32).to_bits().to_le_bytes().to_vec(),
+        ),
+        FieldType::Fixed64 | FieldType::Sfixed64 => (
+            WireType::Fixed64,
+            (integer()? as u64).to_le_bytes().to_vec(),
+        ),
+        FieldType::Fixed32 | FieldType::Sfixed32 => (
+            WireType::Fixed32,
+            (integer()? as u32).to_le_bytes().to_vec(),
+        ),
+        FieldType::String | FieldType::Bytes | FieldType::Message(_) | FieldType::WellKnown(_) => {
+            return Err(text_error(&format!("{word} is not length prefixed")));
+        }
+    };
+    Ok(value)
+}
+
+fn varint_bytes(number: u64) -> (WireType, Vec<u8>) {
+    let mut data = Vec::new();
+    encode_varint(number, &mut data);
+    (WireType::VarInt, data)
+}
+
+/// Parse a decimal or `0x` hex integer. Negative values are returned as two's complement when cast
+fn parse_integer(word: &str) -> Option<i128> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(result) => (true, result),
+        None => (false, word),
+    };
+    let number = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None if digits.bytes().all(|value| value.is_ascii_digit()) => digits.parse().ok()?,
+        None => return None,
+    };
+    if number > i128::from(u64::MAX) {
+        return None;
+    }
+    Some(if negative { -number } else { number })
+}
+
+/// Parse a floating point value. Also accepts `inf`, `-inf` and `nan`
+fn parse_float(word: &str) -> Option<f64> {
+    match word.to_lowercase().as_str() {
+        "inf" | "infinity" => Some(f64::INFINITY),
+        "-inf" | "-infinity" => Some(f64::NEG_INFINITY),
+        "nan" => Some(f64::NAN),
+        _ if word
+            .starts_with(|value: char| value.is_ascii_digit() || value == '-' || value == '.') =>
+        {
+            word.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Split the text into tokens. Comments start with `#` and end at the end of the line
+fn tokenize(text: &str) -> Result<Vec<Token>, SunlightError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(value) = chars.next() {
+        match value {
+            '#' => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            ':' | '{' | '}' | '<' | '>' | '[' | ']' | ',' | ';' => {
+                tokens.push(Token::Symbol(value));
+            }
+            '"' | '\'' => {
+                let mut text = Vec::new();
+                loop {
+                    let next = chars
+                        .next()
+                        .ok_or_else(|| text_error("unterminated string"))?;
+                    match next {
+                        _ if next == value => break,
+                        '\\' => unescape(&mut chars, &mut text)?,
+                        _ => {
+                            let mut buffer = [0; 4];
+                            text.extend_from_slice(next.encode_utf8(&mut buffer).as_bytes());
+                        }
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            _ if value.is_whitespace() => {}
+            _ => {
+                let mut word = String::from(value);
+                while let Some(next) = chars.peek() {
+                    if next.is_alphanumeric() || matches!(next, '_' | '-' | '+' | '.') {
+                        word.push(*next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Decode an escape sequence in a string. Supports the escapes written by `protoc`
+fn unescape(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    text: &mut Vec<u8>,
+) -> Result<(), SunlightError> {
+    let value = chars
+        .next()
+        .ok_or_else(|| text_error("unterminated string"))?;
+    let byte = match value {
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0c,
+        'v' => 0x0b,
+        '0'..='7' => {
+            let mut number = value.to_digit(8).unwrap_or_default();
+            for _ in 0..2 {
+                match chars.peek().and_then(|next| next.to_digit(8)) {
+                    Some(digit) => {
+                        number = number * 8 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            u8::try_from(number).map_err(|_err| text_error("octal escape is too large"))?
+        }
+        'x' => {
+            let mut number = 0;
+            for _ in 0..2 {
+                match chars.peek().and_then(|next| next.to_digit(16)) {
+                    Some(digit) => {
+                        number = number * 16 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            number as u8
+        }
+        '"' | '\'' | '\\' | '?' => value as u8,
+        _ => return Err(text_error(&format!("unknown escape \\{value}"))),
+    };
+    text.push(byte);
+    Ok(())
+}
+
+fn text_error(message: &str) -> SunlightError {
+    error!("[sunlight] could not parse protobuf text format: {message}");
+    SunlightError::Text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_to_protobuf, text_to_protobuf_with_schema};
+    use crate::{
+        light::extract_protobuf,
+        render::text::proto_to_text,
+        schema::typedef::{FieldDef, FieldType, MessageDef},
+    };
+
+    #[test]
+    fn test_text_to_protobuf() {
+        let text = r#"
+            # Comments are ignored
+            1: 1
+            2 {
+              1: "abc"
+            }
+            1: 2
+            3: "\377\000"
+            4: 0x41c676d02e34ecd9
+            5: -1
+            6: 1.5f, 7: [1, 2]
+        "#;
+        let data = text_to_protobuf(text).unwrap();
+        assert_eq!(
+            data,
+            [
+                8, 1, 18, 5, 10, 3, 97, 98, 99, 8, 2, 26, 2, 255, 0, 33, 217, 236, 52, 46, 208,
+                118, 198, 65, 40, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, 53, 0, 0, 192,
+                63, 56, 1, 56, 2
+            ]
+        );
+
+        // Text written by the renderer can be parsed again
+        let proto_map = extract_protobuf(&data).unwrap();
+        assert_eq!(text_to_protobuf(&proto_to_text(&proto_map)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_text_to_protobuf_with_schema() {
+        let mut name = MessageDef::new("Name");
+        name.add_field(FieldDef::new(1, "first", FieldType::String));
+        let mut message = MessageDef::new("Person");
+        message.add_field(FieldDef::new(1, "offset", FieldType::Sint32));
+        message.add_field(FieldDef::new(2, "name", FieldType::Message(name)));
+        message.add_field(FieldDef::new(3, "score", FieldType::Double));
+        message.add_field(FieldDef::new(4, "active", FieldType::Bool));
+
+        let text = r#"offset: -2 name < first: 'a' "bc" > score: 1 active: true 9: 1"#;
+        let data = text_to_protobuf_with_schema(text, &message).unwrap();
+        assert_eq!(
+            data,
+            [
+                8, 3, 18, 5, 10, 3, 97, 98, 99, 25, 0, 0, 0, 0, 0, 0, 240, 63, 32, 1, 72, 1
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_to_protobuf_errors() {
+        assert!(text_to_protobuf("1: \"abc").is_err());
+        assert!(text_to_protobuf("1 { 2: 3").is_err());
+        assert!(text_to_protobuf("name: 1").is_err());
+        assert!(text_to_protobuf("1: abc").is_err());
+        assert!(text_to_protobuf("1: 99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_text_to_protobuf_depth() {
+        let text = format!("{}{}", "1 {".repeat(100), "}".repeat(100));
+        assert!(text_to_protobuf(&text).is_ok());
+        let text = format!("{}{}", "1 {".repeat(101), "}".repeat(101));
+        assert!(text_to_protobuf(&text).is_err());
+        assert!(text_to_protobuf(&"1 {".repeat(200_000)).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    encode::message::encode_protobuf,
+    error::SunlightError,
+    light::{ExtractOptions, extract_protobuf_options},
+};
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub enum Roundtrip {
+    /**Encoding the decoded data produced the exact input bytes */
+    Exact,
+    /**Offset of the first byte that differs. If one side is shorter, this is the length of the shorter side */
+    Mismatch { offset: usize },
+}
+
+/// Decode the provided Protobuf bytes in lossless mode, encode them again and compare the results
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [8, 129, 128, 0, 16, 2, 8, 3];
+/// let result = sunlight::encode::verify::verify_roundtrip(&proto_bytes).unwrap();
+/// assert_eq!(result, sunlight::encode::verify::Roundtrip::Exact);
+/// ```
+pub fn verify_roundtrip(data: &[u8]) -> Result<Roundtrip, SunlightError> {
+    let options = ExtractOptions {
+        lossless: true,
+        ..Default::default()
+    };
+    let proto_map = extract_protobuf_options(data, &options)?;
+    let encoded = encode_protobuf(&proto_map)?;
+
+    let result = match first_difference(data, &encoded) {
+        Some(offset) => Roundtrip::Mismatch { offset },
+        None => Roundtrip::Exact,
+    };
+    Ok(result)
+}
+
+/// Find the first offset where two byte slices differ
+fn first_difference(first: &[u8], second: &[u8]) -> Option<usize> {
+    let offset = first
+        .iter()
+        .zip(second)
+        .position(|(left, right)| left != right);
+
+    match offset {
+        Some(result) => Some(result),
+        None if first.len() != second.len() => Some(first.len().min(second.len())),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Roundtrip, first_difference, verify_roundtrip};
+    use std::{fs::read, path::PathBuf};
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let test = [
+            16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46, 100,
+            117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98, 114, 111,
+            119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
+            0, 0, 0,
+        ];
+        assert_eq!(verify_roundtrip(&test).unwrap(), Roundtrip::Exact);
+    }
+
+    #[test]
+    fn test_verify_roundtrip_blackboxprotobuf() {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data/blackboxprotobuf/test_message.out");
+        let data = read(test_path.to_str().unwrap()).unwrap();
+
+        assert_eq!(verify_roundtrip(&data).unwrap(), Roundtrip::Exact);
+    }
+
+    #[test]
+    fn test_verify_roundtrip_duplicates() {
+        // Field 1 appears twice with a packed field 3 and a group wire type at the end
+        let test = [8, 1, 26, 3, 1, 2, 3, 8, 255, 255, 3, 35, 1, 2];
+        assert_eq!(verify_roundtrip(&test).unwrap(), Roundtrip::Exact);
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference(&[1, 2, 3], &[1, 2, 3]), None);
+        assert_eq!(first_difference(&[1, 2, 3], &[1, 5, 3]), Some(1));
+        assert_eq!(first_difference(&[1, 2, 3], &[1, 2]), Some(2));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::light::{Tag, WireType};
+
+/// Encode a base 128 varint
+pub(crate) fn encode_varint(value: u64, data: &mut Vec<u8>) {
+    let mut remaining = value;
+    let adjust = 0x7f;
+    let more = 0x80;
+    let wire = 7;
+    while remaining > adjust {
+        data.push((remaining & adjust) as u8 | more);
+        remaining >>= wire;
+    }
+    data.push(remaining as u8);
+}
+
+/// Encode a field tag. Unknown wire types reuse the wire type bits from the original tag byte
+pub(crate) fn encode_tag(tag: &Tag, data: &mut Vec<u8>) {
+    let field_number = 3;
+    let wire = match tag.wire_type {
+        WireType::VarInt => 0,
+        WireType::Fixed64 => 1,
+        WireType::Len => 2,
+        WireType::StartGroup => 3,
+        WireType::EndGroup => 4,
+        WireType::Fixed32 => 5,
+        WireType::Unknown => tag.tag_byte & 7,
+    };
+    encode_varint(((tag.field as u64) << field_number) | wire as u64, data);
+}
+
+/// Encode a length prefixed payload
+pub(crate) fn encode_length(payload: &[u8], data: &mut Vec<u8>) {
+    encode_varint(payload.len() as u64, data);
+    data.extend_from_slice(payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_length, encode_tag, encode_varint};
+    use crate::light::{Tag, WireType};
+
+    #[test]
+    fn test_encode_varint() {
+        let mut data = Vec::new();
+        encode_varint(130288, &mut data);
+        assert_eq!(data, [240, 249, 7]);
+
+        data.clear();
+        encode_varint(u64::MAX, &mut data);
+        assert_eq!(data, [255, 255, 255, 255, 255, 255, 255, 255, 255, 1]);
+    }
+
+    #[test]
+    fn test_encode_tag() {
+        let tag = Tag {
+            tag_byte: 226,
+            wire_type: WireType::Len,
+            field: 300,
+        };
+        let mut data = Vec::new();
+        encode_tag(&tag, &mut data);
+        assert_eq!(data, [226, 18]);
+    }
+
+    #[test]
+    fn test_encode_length() {
+        let mut data = Vec::new();
+        encode_length(b"abc", &mut data);
+        assert_eq!(data, [3, 97, 98, 99]);
+    }
+}
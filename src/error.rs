@@ -1,8 +1,16 @@
+use log::error;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SunlightError {
     Parser,
+    Encoder,
+    Path,
+    Text,
+    Io,
+    Thread,
+    Deserialize,
+    Serialize,
 }
 
 impl std::error::Error for SunlightError {}
@@ -11,6 +19,29 @@ impl fmt::Display for SunlightError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             SunlightError::Parser => write!(f, "Could not parse provided protobuf bytes"),
+            SunlightError::Encoder => write!(f, "Could not encode provided protobuf values"),
+            SunlightError::Path => write!(f, "Could not find provided field path"),
+            SunlightError::Text => write!(f, "Could not parse provided protobuf text format"),
+            SunlightError::Io => write!(f, "Could not read or write provided files"),
+            SunlightError::Thread => write!(f, "Could not start worker threads"),
+            SunlightError::Deserialize => {
+                write!(f, "Could not deserialize provided protobuf bytes")
+            }
+            SunlightError::Serialize => write!(f, "Could not serialize provided value"),
         }
     }
 }
+
+impl serde::de::Error for SunlightError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        error!("[sunlight] could not deserialize protobuf bytes: {msg}");
+        SunlightError::Deserialize
+    }
+}
+
+impl serde::ser::Error for SunlightError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        error!("[sunlight] could not serialize value: {msg}");
+        SunlightError::Serialize
+    }
+}
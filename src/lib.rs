@@ -1,4 +1,5 @@
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))]
 #![warn(
     clippy::all,
     clippy::await_holding_lock,
@@ -7,7 +8,7 @@
     clippy::dbg_macro,
     clippy::debug_assert_with_mut_call,
     clippy::doc_markdown,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::exit,
     clippy::expl_impl_clone_on_copy,
@@ -57,7 +58,6 @@
     clippy::string_add_assign,
     clippy::string_add,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::todo,
     clippy::trait_duplication_in_bounds,
     clippy::unimplemented,
@@ -71,7 +71,23 @@
     rust_2018_idioms
 )]
 
+pub mod annotate;
+pub mod batch;
+pub mod de;
+pub mod delimited;
+pub mod diff;
+pub mod encode;
 mod error;
 pub mod light;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod query;
+pub mod render;
+pub mod schema;
+pub mod ser;
 mod tags;
 mod utils;
+pub mod view;
+pub mod visit;
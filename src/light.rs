@@ -1,16 +1,227 @@
-use crate::{error::SunlightError, tags::parser::parse_tag};
+use crate::{
+    error::SunlightError,
+    render::json::{JsonOptions, value_to_json},
+    schema::typedef::{FieldType, MessageDef},
+    tags::parser::parse_tag,
+};
 use log::error;
-use serde::Serialize;
-use serde_json::Value;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ProtoTag {
     pub tag: Tag,
-    pub value: Value,
+    pub value: ProtoValue,
+    /**Where each value was found. Repeated fields have one record per value */
+    #[serde(skip)]
+    pub records: Vec<WireRecord>,
 }
 
-#[derive(Debug, Serialize)]
+/// Records are not compared. They only describe where the values were found
+impl PartialEq for ProtoTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.value == other.value
+    }
+}
+
+/// A decoded Protobuf value. Use `render::json` or `serde` to get the JSON representation
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoValue {
+    /**int32, int64, uint32, uint64, sint32, sint64, bool, or enum */
+    VarInt(u64),
+    /**fixed32, sfixed32 or float */
+    Fixed32(u32),
+    /**fixed64, sfixed64 or double */
+    Fixed64(u64),
+    String(String),
+    Bytes(Vec<u8>),
+    /**Sub-message. Fields are keyed by field number */
+    Message(HashMap<usize, ProtoTag>),
+    /**Scalars packed into one length prefixed value. The parser cannot tell these apart from bytes, use `schema::decode::unpack_with_schema` to convert fields declared as repeated scalars */
+    Packed(Vec<ProtoValue>),
+    /**Remaining bytes after a deprecated group or unknown wire type. Parsing ends there */
+    Group(Vec<u8>),
+    /**Every value of a field that appears more than once, or of a field that `ExtractOptions::arrays` says is repeated */
+    Repeated(Vec<ProtoValue>),
+}
+
+impl ProtoValue {
+    /// Get the values of a field. Returns one value unless the field is repeated
+    pub fn values(&self) -> &[ProtoValue] {
+        match self {
+            ProtoValue::Repeated(values) => values,
+            _ => std::slice::from_ref(self),
+        }
+    }
+
+    /// Get mutable values of a field. Returns one value unless the field is repeated
+    pub fn values_mut(&mut self) -> &mut [ProtoValue] {
+        match self {
+            ProtoValue::Repeated(values) => values,
+            _ => std::slice::from_mut(self),
+        }
+    }
+
+    /// Get the wire type used to encode the value
+    pub fn wire_type(&self) -> WireType {
+        match self {
+            ProtoValue::VarInt(_) => WireType::VarInt,
+            ProtoValue::Fixed32(_) => WireType::Fixed32,
+            ProtoValue::Fixed64(_) => WireType::Fixed64,
+            ProtoValue::String(_)
+            | ProtoValue::Bytes(_)
+            | ProtoValue::Message(_)
+            | ProtoValue::Packed(_) => WireType::Len,
+            ProtoValue::Group(_) => WireType::StartGroup,
+            ProtoValue::Repeated(values) => {
+                values.first().map_or(WireType::Len, ProtoValue::wire_type)
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ProtoValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_message(&self) -> Option<&HashMap<usize, ProtoTag>> {
+        match self {
+            ProtoValue::Message(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes to the same JSON as `render::json::value_to_json` with the default options
+impl Serialize for ProtoValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        value_to_json(self, &JsonOptions::default()).serialize(serializer)
+    }
+}
+
+/// Location and raw bytes of a single field value in the decoded data
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WireRecord {
+    /**Offset to the field tag from the start of the decoded data */
+    pub offset: usize,
+    /**Length prefixed payload bytes. Only kept for `Len` fields when decoding with `ExtractOptions::lossless`, for strings that had trailing NULL characters removed, or for sub-messages that stopped at a group */
+    pub payload: Option<Vec<u8>>,
+    /**Exact bytes of the tag and value. Sub-messages only keep the tag and length prefix, their payload is rebuilt from their fields. Only kept when decoding with `ExtractOptions::lossless` */
+    pub raw: Option<Vec<u8>>,
+    /**The value changed after decoding. The kept bytes are out of date and are never reused when encoding */
+    pub edited: bool,
+}
+
+impl WireRecord {
+    /// Mark the value as changed. Call this after changing a decoded value directly, otherwise the original bytes are encoded instead of the new value
+    pub fn mark_edited(&mut self) {
+        self.edited = true;
+        self.payload = None;
+        self.raw = None;
+    }
+}
+
+/// Options to control how Protobuf data is extracted
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /**Keep the exact bytes of every field. Allows non-minimal varints, packed values, field order and duplicate fields to be encoded back exactly */
+    pub lossless: bool,
+    /**Which fields are returned as `ProtoValue::Repeated` when they are only seen once */
+    pub arrays: ArrayMode,
+}
+
+/// Controls which fields are always returned as `ProtoValue::Repeated`. Use this to keep the output shape the same across messages
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ArrayMode {
+    /**Fields become repeated the second time they are seen. The same field can be a single value in one message and repeated in the next */
+    #[default]
+    Seen,
+    /**Every field is repeated, even if it is only seen once */
+    Always,
+    /**Fields in the hints are always repeated. Other fields become repeated the second time they are seen */
+    Hints(RepeatedHints),
+}
+
+impl ArrayMode {
+    /// Check if a field seen for the first time should be repeated. Path is the field numbers of the parent sub-messages
+    pub(crate) fn is_repeated(&self, path: &[usize], field: usize) -> bool {
+        match self {
+            ArrayMode::Seen => false,
+            ArrayMode::Always => true,
+            ArrayMode::Hints(hints) => hints.contains_field(path, field),
+        }
+    }
+}
+
+/// Fields known to be repeated. Fields are found by their path of field numbers from the top level message, so `[2, 1]` is field 1 inside the sub-message in field 2
+///
+/// # Example
+/// ```rust
+/// use sunlight::light::{ArrayMode, ExtractOptions, ProtoValue, RepeatedHints, extract_protobuf_options};
+///
+/// let mut hints = RepeatedHints::new();
+/// hints.add(&[2, 1]);
+/// let options = ExtractOptions {
+///     arrays: ArrayMode::Hints(hints),
+///     ..Default::default()
+/// };
+///
+/// // Field 1 inside field 2 is only seen once
+/// let proto_bytes = [8, 1, 18, 3, 8, 150, 1];
+/// let proto_map = extract_protobuf_options(&proto_bytes, &options).unwrap();
+/// let nested = proto_map[&2].value.as_message().unwrap();
+/// assert_eq!(nested[&1].value, ProtoValue::Repeated(vec![ProtoValue::VarInt(150)]));
+/// assert_eq!(proto_map[&1].value, ProtoValue::VarInt(1));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepeatedHints {
+    paths: HashSet<Vec<usize>>,
+}
+
+impl RepeatedHints {
+    pub fn new() -> RepeatedHints {
+        RepeatedHints::default()
+    }
+
+    /// Get the repeated fields declared in a message definition and its sub-messages
+    pub fn from_schema(message: &MessageDef) -> RepeatedHints {
+        let mut hints = RepeatedHints::new();
+        hints.add_message(message, &mut Vec::new());
+        hints
+    }
+
+    /// Mark the field at the path as repeated
+    pub fn add(&mut self, path: &[usize]) {
+        self.paths.insert(path.to_vec());
+    }
+
+    pub fn contains(&self, path: &[usize]) -> bool {
+        self.paths.contains(path)
+    }
+
+    fn contains_field(&self, path: &[usize], field: usize) -> bool {
+        let mut full_path = path.to_vec();
+        full_path.push(field);
+        self.paths.contains(&full_path)
+    }
+
+    fn add_message(&mut self, message: &MessageDef, path: &mut Vec<usize>) {
+        for (number, field) in &message.fields {
+            path.push(*number);
+            if field.repeated {
+                self.paths.insert(path.clone());
+            }
+            if let FieldType::Message(sub) = &field.field_type {
+                self.add_message(sub, path);
+            }
+            path.pop();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Tag {
     pub tag_byte: u8,
     pub wire_type: WireType,
@@ -18,7 +229,7 @@ pub struct Tag {
     pub field: usize,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WireType {
     VarInt,
     Fixed64,
@@ -35,6 +246,8 @@ pub enum WireType {
 ///
 /// # Example
 /// ```rust
+/// use sunlight::light::ProtoValue;
+///
 /// let proto_bytes = [
 ///           10, 45, 99, 111, 109, 46, 97, 112, 112, 108, 101, 46, 97, 112, 112, 115, 116, 111, 114,
 ///           101, 100, 46, 77, 105, 103, 114, 97, 116, 111, 114, 77, 105, 115, 99, 101, 108, 108,
@@ -46,10 +259,10 @@ pub enum WireType {
 /// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
 /// assert_eq!(
 ///       proto_map.get(&1).unwrap().value,
-///       serde_json::Value::Array(vec![
-///       serde_json::Value::String(String::from("com.apple.appstored.MigratorMiscellaneousTask")),
-///       serde_json::Value::String(String::from("com.apple.appstored.MigratorAppUsageTask")),
-///       serde_json::Value::String(String::from("com.apple.appstored.MigratorArcadeTask"))]));
+///       ProtoValue::Repeated(vec![
+///       ProtoValue::String(String::from("com.apple.appstored.MigratorMiscellaneousTask")),
+///       ProtoValue::String(String::from("com.apple.appstored.MigratorAppUsageTask")),
+///       ProtoValue::String(String::from("com.apple.appstored.MigratorArcadeTask"))]));
 /// assert_eq!(proto_map.get(&1).unwrap().tag.wire_type, sunlight::light::WireType::Len);
 /// ```
 /** ```json
@@ -72,7 +285,27 @@ pub enum WireType {
 ```
 */
 pub fn extract_protobuf(data: &[u8]) -> Result<HashMap<usize, ProtoTag>, SunlightError> {
-    let proto_result = parse_tag(data);
+    extract_protobuf_options(data, &ExtractOptions::default())
+}
+
+/// Attempt to extract data from provided Protobuf bytes using the provided options. Returns a `HashMap` representing the Protobuf data
+///
+/// # Example
+/// ```rust
+/// // Field 2 uses a non-minimal varint for the value 1
+/// let proto_bytes = [8, 150, 1, 16, 129, 128, 0];
+/// let options = sunlight::light::ExtractOptions {
+///     lossless: true,
+///     ..Default::default()
+/// };
+/// let proto_map = sunlight::light::extract_protobuf_options(&proto_bytes, &options).unwrap();
+/// assert_eq!(proto_map.get(&2).unwrap().records[0].raw, Some(vec![16, 129, 128, 0]));
+/// ```
+pub fn extract_protobuf_options(
+    data: &[u8],
+    options: &ExtractOptions,
+) -> Result<HashMap<usize, ProtoTag>, SunlightError> {
+    let proto_result = parse_tag(data, 0, options);
     let proto_map = match proto_result {
         Ok((_, results)) => results,
         Err(err) => {
@@ -86,7 +319,11 @@ pub fn extract_protobuf(data: &[u8]) -> Result<HashMap<usize, ProtoTag>, Sunligh
 
 #[cfg(test)]
 mod tests {
-    use super::extract_protobuf;
+    use super::{
+        ArrayMode, ExtractOptions, ProtoValue, RepeatedHints, extract_protobuf,
+        extract_protobuf_options,
+    };
+    use crate::schema::typedef::infer_typedef;
 
     #[test]
     #[should_panic(expected = "Parser")]
@@ -94,4 +331,24 @@ mod tests {
         let bad_data = [0, 0, 1, 4, 5, 0, 0];
         let _ = extract_protobuf(&bad_data).unwrap();
     }
+
+    #[test]
+    fn test_repeated_hints_from_schema() {
+        // Field 1 in the sub-message is repeated in the first message but only seen once in the second
+        let first = extract_protobuf(&[10, 6, 8, 150, 1, 8, 151, 1]).unwrap();
+        let hints = RepeatedHints::from_schema(&infer_typedef(&first));
+        assert!(hints.contains(&[1, 1]));
+        assert!(!hints.contains(&[1]));
+
+        let options = ExtractOptions {
+            arrays: ArrayMode::Hints(hints),
+            ..Default::default()
+        };
+        let second = extract_protobuf_options(&[10, 3, 8, 152, 1], &options).unwrap();
+        let nested = second[&1].value.as_message().unwrap();
+        assert_eq!(
+            nested[&1].value,
+            ProtoValue::Repeated(vec![ProtoValue::VarInt(152)])
+        );
+    }
 }
@@ -0,0 +1,114 @@
+use crate::{
+    delimited::{Delimited, iter_delimited},
+    error::SunlightError,
+    light::{ExtractOptions, ProtoTag, extract_protobuf_options},
+    view::MessageView,
+};
+use log::error;
+use memmap2::Mmap;
+use std::{collections::HashMap, fs::File, path::Path};
+
+/// A file mapped into memory. Views and delimited records borrow from the mapping so the file is never copied
+///
+/// # Example
+/// ```rust
+/// use sunlight::mmap::MappedFile;
+///
+/// // SAFETY: Test data is not modified while the example runs
+/// let file =
+///     unsafe { MappedFile::open("tests/test_data/blackboxprotobuf/test_message.out") }.unwrap();
+/// let fields = file.view().fields().count();
+/// assert!(fields > 0);
+/// assert!(file.extract().is_ok());
+/// ```
+#[derive(Debug)]
+pub struct MappedFile {
+    /**Empty files cannot be mapped on every platform */
+    map: Option<Mmap>,
+}
+
+impl MappedFile {
+    /// Map a file into memory
+    ///
+    /// # Safety
+    /// The file must not be truncated or modified, by this or any other process, while the `MappedFile` or anything borrowed from it is alive.
+    /// Truncating a mapped file can crash the process with `SIGBUS` and modifying it is undefined behavior.
+    /// Read a copy of the file with `std::fs::read` instead if the file may still be in use, such as the database of a running app
+    #[allow(unsafe_code)]
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile, SunlightError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| {
+            error!("[sunlight] could not open {}: {err:?}", path.display());
+            SunlightError::Io
+        })?;
+        let length = file.metadata().map(|meta| meta.len()).unwrap_or_default();
+        if length == 0 {
+            return Ok(MappedFile { map: None });
+        }
+
+        // SAFETY: The caller guarantees the file is not changed while mapped
+        let map = unsafe { Mmap::map(&file) }.map_err(|err| {
+            error!("[sunlight] could not map {}: {err:?}", path.display());
+            SunlightError::Io
+        })?;
+        Ok(MappedFile { map: Some(map) })
+    }
+
+    /// Bytes of the mapped file
+    pub fn bytes(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or_default()
+    }
+
+    /// Zero copy view of the file as a single message
+    pub fn view(&self) -> MessageView<'_> {
+        MessageView::new(self.bytes())
+    }
+
+    /// Iterate through the file as a stream of length delimited messages. Records borrow from the mapping
+    pub fn delimited(&self) -> Delimited<'_> {
+        iter_delimited(self.bytes())
+    }
+
+    /// Decode the file as a single message
+    pub fn extract(&self) -> Result<HashMap<usize, ProtoTag>, SunlightError> {
+        self.extract_options(&ExtractOptions::default())
+    }
+
+    /// Decode the file as a single message using the provided options
+    pub fn extract_options(
+        &self,
+        options: &ExtractOptions,
+    ) -> Result<HashMap<usize, ProtoTag>, SunlightError> {
+        extract_protobuf_options(self.bytes(), options)
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappedFile;
+    use crate::light::extract_protobuf;
+    use std::{fs::read, path::PathBuf};
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_mapped_file() {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data/blackboxprotobuf/test_message.out");
+
+        // SAFETY: Test data is not modified while the test runs
+        let file = unsafe { MappedFile::open(&test_path) }.unwrap();
+        assert_eq!(file.bytes(), read(&test_path).unwrap());
+        assert_eq!(
+            file.extract().unwrap(),
+            extract_protobuf(&read(&test_path).unwrap()).unwrap()
+        );
+        assert!(file.view().fields().all(|field| field.is_ok()));
+        assert!(unsafe { MappedFile::open(test_path.with_extension("missing")) }.is_err());
+    }
+}
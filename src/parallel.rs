@@ -0,0 +1,258 @@
+use crate::{
+    batch::{BatchOptions, BatchSummary, WalkEntry, collect_files, decode_entry, write_records},
+    delimited::iter_delimited,
+    error::SunlightError,
+    light::{ProtoTag, extract_protobuf},
+};
+use log::error;
+use rayon::{ThreadPool, ThreadPoolBuilder, prelude::*};
+use std::{collections::HashMap, io::Write, path::Path};
+
+/// Options to control parallel decoding
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    /**Number of worker threads. 0 uses one thread per CPU */
+    pub threads: usize,
+    /**Maximum input bytes decoded at once. Results are handed back in order before more input is decoded, so this bounds the memory held by results in flight. A single input larger than this is still decoded */
+    pub max_bytes_in_flight: usize,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        ParallelOptions {
+            threads: 0,
+            max_bytes_in_flight: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Result of decoding one buffer
+pub type ExtractResult = Result<HashMap<usize, ProtoTag>, SunlightError>;
+
+/// Decode independent Protobuf buffers in parallel. Results are passed to `sink` in input order with the index of the buffer
+///
+/// # Example
+/// ```rust
+/// use sunlight::parallel::{ParallelOptions, extract_parallel};
+///
+/// let buffers: Vec<&[u8]> = vec![&[8, 1], &[18, 5], &[8, 150, 1]];
+/// let mut results = Vec::new();
+/// extract_parallel(buffers, &ParallelOptions::default(), |index, result| {
+///     results.push((index, result.is_ok()));
+/// })
+/// .unwrap();
+/// assert_eq!(results, [(0, true), (1, false), (2, true)]);
+/// ```
+pub fn extract_parallel<'a, I, F>(
+    buffers: I,
+    options: &ParallelOptions,
+    mut sink: F,
+) -> Result<(), SunlightError>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+    F: FnMut(usize, ExtractResult),
+{
+    let pool = build_pool(options)?;
+    let mut index = 0;
+    for_each_window(
+        &pool,
+        buffers,
+        |data| data.len(),
+        options.max_bytes_in_flight,
+        extract_protobuf,
+        |result| {
+            sink(index, result);
+            index += 1;
+            Ok(())
+        },
+    )
+}
+
+/// Decode a stream of length delimited messages in parallel. Results are passed to `sink` in stream order with the offset of each record.
+/// If the stream cannot be split into records the error is passed to `sink` with the offset of the bad record and decoding stops
+pub fn extract_delimited_parallel<F>(
+    data: &[u8],
+    options: &ParallelOptions,
+    mut sink: F,
+) -> Result<(), SunlightError>
+where
+    F: FnMut(usize, ExtractResult),
+{
+    let pool = build_pool(options)?;
+    let mut stream = iter_delimited(data);
+    let mut failed = None;
+    let records = std::iter::from_fn(|| match stream.next()? {
+        Ok(record) => Some(record),
+        Err(err) => {
+            failed = Some((stream.offset(), err));
+            None
+        }
+    });
+    for_each_window(
+        &pool,
+        records,
+        |record| record.data.len(),
+        options.max_bytes_in_flight,
+        |record| (record.offset, extract_protobuf(record.data)),
+        |(offset, result)| {
+            sink(offset, result);
+            Ok(())
+        },
+    )?;
+
+    if let Some((offset, err)) = failed {
+        sink(offset, Err(err));
+    }
+    Ok(())
+}
+
+/// Decode every file under a directory in parallel and write one JSON Lines record per message. Output is identical to `batch_directory`
+pub fn batch_directory_parallel<W: Write>(
+    directory: &Path,
+    options: &BatchOptions,
+    parallel: &ParallelOptions,
+    writer: &mut W,
+) -> Result<BatchSummary, SunlightError> {
+    let pool = build_pool(parallel)?;
+    let mut summary = BatchSummary::default();
+    for_each_window(
+        &pool,
+        collect_files(directory)?,
+        |entry| match entry {
+            WalkEntry::File(path) => path
+                .metadata()
+                .map_or(0, |meta| usize::try_from(meta.len()).unwrap_or(usize::MAX)),
+            WalkEntry::Unreadable { .. } => 0,
+        },
+        parallel.max_bytes_in_flight,
+        |entry| {
+            let is_file = matches!(entry, WalkEntry::File(_));
+            (is_file, decode_entry(&entry, options))
+        },
+        |(is_file, records)| {
+            if is_file {
+                summary.files += 1;
+            }
+            write_records(&records, &mut summary, writer)
+        },
+    )?;
+    Ok(summary)
+}
+
+fn build_pool(options: &ParallelOptions) -> Result<ThreadPool, SunlightError> {
+    ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()
+        .map_err(|err| {
+            error!("[sunlight] could not start worker threads: {err:?}");
+            SunlightError::Thread
+        })
+}
+
+/// Group items into windows of at most `max_bytes` and decode each window in parallel. Results are passed to `sink` in input order before the next window starts
+fn for_each_window<T, R, I, S, W, K>(
+    pool: &ThreadPool,
+    items: I,
+    size: S,
+    max_bytes: usize,
+    work: W,
+    mut sink: K,
+) -> Result<(), SunlightError>
+where
+    T: Send,
+    R: Send,
+    I: IntoIterator<Item = T>,
+    S: Fn(&T) -> usize,
+    W: Fn(T) -> R + Sync,
+    K: FnMut(R) -> Result<(), SunlightError>,
+{
+    let mut window = Vec::new();
+    let mut window_bytes = 0;
+    let mut flush = |window: &mut Vec<T>| {
+        let results: Vec<R> = pool.install(|| window.par_drain(..).map(&work).collect());
+        results.into_iter().try_for_each(&mut sink)
+    };
+
+    for item in items {
+        let item_bytes = size(&item);
+        if !window.is_empty() && window_bytes + item_bytes > max_bytes {
+            flush(&mut window)?;
+            window_bytes = 0;
+        }
+        window_bytes += item_bytes;
+        window.push(item);
+    }
+    flush(&mut window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ParallelOptions, batch_directory_parallel, extract_delimited_parallel, extract_parallel,
+    };
+    use crate::{
+        batch::{BatchOptions, batch_directory},
+        light::ProtoValue,
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_extract_parallel() {
+        let buffers: Vec<Vec<u8>> = (0..100u8).map(|value| vec![8, value]).collect();
+        // Small windows force several rounds of decoding
+        let options = ParallelOptions {
+            threads: 4,
+            max_bytes_in_flight: 16,
+        };
+        let mut values = Vec::new();
+        extract_parallel(
+            buffers.iter().map(Vec::as_slice),
+            &options,
+            |index, result| {
+                let proto_map = result.unwrap();
+                if let ProtoValue::VarInt(value) = proto_map[&1].value {
+                    values.push((index, value));
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(values.len(), 100);
+        assert!(
+            values
+                .iter()
+                .enumerate()
+                .all(|(index, value)| *value == (index, index as u64))
+        );
+    }
+
+    #[test]
+    fn test_extract_delimited_parallel() {
+        // The second record is not a message and the third is missing a byte
+        let test = [2, 8, 1, 2, 18, 5, 3, 18, 1];
+        let mut results = Vec::new();
+        extract_delimited_parallel(&test, &ParallelOptions::default(), |offset, result| {
+            results.push((offset, result.is_ok()));
+        })
+        .unwrap();
+        assert_eq!(results, [(0, true), (3, false), (6, false)]);
+    }
+
+    #[test]
+    fn test_batch_directory_parallel() {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data");
+
+        let mut expected = Vec::new();
+        batch_directory(&test_path, &BatchOptions::default(), &mut expected).unwrap();
+        let mut output = Vec::new();
+        let options = ParallelOptions {
+            threads: 2,
+            max_bytes_in_flight: 1,
+        };
+        let summary =
+            batch_directory_parallel(&test_path, &BatchOptions::default(), &options, &mut output)
+                .unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(output, expected);
+    }
+}
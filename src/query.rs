@@ -0,0 +1,305 @@
+use crate::{
+    encode::edit::{join_step, split_step},
+    error::SunlightError,
+    light::{ExtractOptions, ProtoTag, ProtoValue},
+    tags::parser::parse_tag,
+};
+use log::error;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A query such as `2.1[*].5` or `4.*:string`. Evaluates against a decoded message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub steps: Vec<QueryStep>,
+    /**Only return values of this type */
+    pub value_type: Option<ValueType>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStep {
+    /**Field number to match. `None` matches every field */
+    pub field: Option<usize>,
+    /**Which value of a repeated field to match. `None` matches every value */
+    pub index: Option<usize>,
+}
+
+/// Types that query results can be filtered by
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ValueType {
+    VarInt,
+    Fixed32,
+    Fixed64,
+    String,
+    Bytes,
+    Message,
+    Group,
+}
+
+/// A value found by a query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryMatch {
+    /**Path to the value, such as `2.1[1].5`. Can be used with `FieldPath::parse` */
+    pub path: String,
+    /**Offset to the field tag from the start of the decoded data. Only known if the message was decoded from bytes */
+    pub offset: Option<usize>,
+    pub value: ProtoValue,
+}
+
+impl Query {
+    /// Parse a query. Steps are separated by `.` and `*` matches any field.
+    /// A step without `[index]` matches every value of a repeated field, same as `[*]`. A type filter such as `:string` may be added to the end
+    pub fn parse(query: &str) -> Result<Query, SunlightError> {
+        let (path, value_type) = match query.rsplit_once(':') {
+            Some((path, filter)) => {
+                let value_type = match filter.trim().to_lowercase().as_str() {
+                    "varint" => ValueType::VarInt,
+                    "fixed32" => ValueType::Fixed32,
+                    "fixed64" => ValueType::Fixed64,
+                    "string" => ValueType::String,
+                    "bytes" => ValueType::Bytes,
+                    "message" => ValueType::Message,
+                    "group" => ValueType::Group,
+                    _ => return Err(query_error(query)),
+                };
+                (path, Some(value_type))
+            }
+            None => (query, None),
+        };
+
+        let mut steps = Vec::new();
+        for step in path.split('.') {
+            let (field, index) = split_step(step).ok_or_else(|| query_error(query))?;
+            let field = match field {
+                "*" => None,
+                _ => Some(field.parse().map_err(|_err| query_error(query))?),
+            };
+            let index = match index {
+                None | Some("*") => None,
+                Some(value) => Some(value.parse().map_err(|_err| query_error(query))?),
+            };
+            steps.push(QueryStep { field, index });
+        }
+        Ok(Query { steps, value_type })
+    }
+
+    /// Get every value that matches the query. Returns nothing if the path does not exist
+    pub fn run(&self, proto_map: &HashMap<usize, ProtoTag>) -> Vec<QueryMatch> {
+        let mut matches = Vec::new();
+        self.run_message(proto_map, &self.steps, "", &mut matches);
+        matches
+    }
+
+    /// Match the remaining steps against a message
+    fn run_message(
+        &self,
+        proto_map: &HashMap<usize, ProtoTag>,
+        steps: &[QueryStep],
+        parent: &str,
+        matches: &mut Vec<QueryMatch>,
+    ) {
+        let Some((step, remaining)) = steps.split_first() else {
+            return;
+        };
+
+        let mut fields: Vec<&ProtoTag> = match step.field {
+            Some(field) => proto_map.get(&field).into_iter().collect(),
+            None => proto_map.values().collect(),
+        };
+        fields.sort_by_key(|proto_tag| proto_tag.tag.field);
+
+        for proto_tag in fields {
+            let field = proto_tag.tag.field;
+            let values = proto_tag.value.values();
+            let repeated = values.len() > 1;
+
+            for (index, entry) in values.iter().enumerate() {
+                if step.index.is_some_and(|wanted| wanted != index) {
+                    continue;
+                }
+                let path = join_step(parent, field, repeated.then_some(index));
+                let offset = proto_tag.records.get(index).map(|record| record.offset);
+
+                if remaining.is_empty() {
+                    if self
+                        .value_type
+                        .is_none_or(|value_type| value_type == type_of(entry))
+                    {
+                        matches.push(QueryMatch {
+                            path,
+                            offset,
+                            value: entry.clone(),
+                        });
+                    }
+                    continue;
+                }
+
+                match entry {
+                    ProtoValue::Message(sub) => self.run_message(sub, remaining, &path, matches),
+                    // Strings and bytes may also be sub-messages
+                    ProtoValue::String(_) | ProtoValue::Bytes(_) => {
+                        let record = proto_tag.records.get(index);
+                        let payload = match (entry, record.and_then(|value| value.payload.as_ref()))
+                        {
+                            (_, Some(payload)) => payload.as_slice(),
+                            (ProtoValue::String(text), None) => text.as_bytes(),
+                            (ProtoValue::Bytes(bytes), None) => bytes.as_slice(),
+                            _ => continue,
+                        };
+                        // Offsets are only known if the exact field bytes were kept
+                        let payload_offset = record
+                            .and_then(|value| value.raw.as_ref())
+                            .zip(offset)
+                            .map(|(raw, start)| start + raw.len() - payload.len());
+                        let Ok((_, sub)) = parse_tag(
+                            payload,
+                            payload_offset.unwrap_or_default(),
+                            &ExtractOptions::default(),
+                        ) else {
+                            continue;
+                        };
+                        let before = matches.len();
+                        self.run_message(&sub, remaining, &path, matches);
+                        if payload_offset.is_none() {
+                            for entry in &mut matches[before..] {
+                                entry.offset = None;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Get every value in the decoded message that matches the query
+///
+/// # Example
+/// ```rust
+/// use sunlight::{light::ProtoValue, query::query_values};
+///
+/// // Field 2 is a repeated sub-message containing a string in field 1
+/// let proto_bytes = [8, 1, 18, 3, 10, 1, 97, 18, 3, 10, 1, 98];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// let result = query_values(&proto_map, "2.1:string").unwrap();
+/// assert_eq!(result[1].path, "2[1].1");
+/// assert_eq!(result[1].value, ProtoValue::String(String::from("b")));
+/// ```
+pub fn query_values(
+    proto_map: &HashMap<usize, ProtoTag>,
+    query: &str,
+) -> Result<Vec<QueryMatch>, SunlightError> {
+    Ok(Query::parse(query)?.run(proto_map))
+}
+
+/// Get the type used to filter a value
+fn type_of(value: &ProtoValue) -> ValueType {
+    match value {
+        ProtoValue::VarInt(_) => ValueType::VarInt,
+        ProtoValue::Fixed32(_) => ValueType::Fixed32,
+        ProtoValue::Fixed64(_) => ValueType::Fixed64,
+        ProtoValue::String(_) => ValueType::String,
+        ProtoValue::Bytes(_) | ProtoValue::Packed(_) | ProtoValue::Repeated(_) => ValueType::Bytes,
+        ProtoValue::Message(_) => ValueType::Message,
+        ProtoValue::Group(_) => ValueType::Group,
+    }
+}
+
+fn query_error(query: &str) -> SunlightError {
+    error!("[sunlight] could not parse query: {query}");
+    SunlightError::Path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Query, QueryStep, ValueType, query_values};
+    use crate::{
+        encode::edit::{FieldPath, PathStep},
+        light::{ExtractOptions, ProtoValue, extract_protobuf, extract_protobuf_options},
+    };
+
+    #[test]
+    fn test_query_parse() {
+        let result = Query::parse("2.1[*].5[1].*:message").unwrap();
+        assert_eq!(result.steps.len(), 4);
+        assert_eq!(
+            result.steps[2],
+            QueryStep {
+                field: Some(5),
+                index: Some(1)
+            }
+        );
+        assert_eq!(
+            result.steps[3],
+            QueryStep {
+                field: None,
+                index: None
+            }
+        );
+        assert_eq!(result.value_type, Some(ValueType::Message));
+
+        assert!(Query::parse("2.a").is_err());
+        assert!(Query::parse("2[1").is_err());
+        assert!(Query::parse("2:float").is_err());
+    }
+
+    #[test]
+    fn test_query_values() {
+        // Field 4 is a sub-message with a varint, a string and a double
+        let test = [
+            8, 1, 34, 17, 8, 150, 1, 18, 3, 97, 98, 99, 25, 217, 236, 52, 46, 208, 118, 198, 65, 8,
+            2,
+        ];
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let proto_map = extract_protobuf_options(&test, &options).unwrap();
+
+        let result = query_values(&proto_map, "4.*").unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].path, "4.1");
+        assert_eq!(result[0].offset, Some(4));
+        assert_eq!(result[0].value, ProtoValue::VarInt(150));
+        assert_eq!(result[2].offset, Some(12));
+
+        let result = query_values(&proto_map, "4.*:fixed64").unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].path, "4.3");
+
+        let result = query_values(&proto_map, "1[1]").unwrap();
+        assert_eq!(result[0].path, "1[1]");
+        assert_eq!(result[0].value, ProtoValue::VarInt(2));
+
+        // Paths of matches use the same syntax as field paths
+        let result = query_values(&proto_map, "*.*").unwrap();
+        assert_eq!(result.len(), 3);
+        for entry in result {
+            assert!(FieldPath::parse(&entry.path).is_ok());
+        }
+        let path = FieldPath::parse(&query_values(&proto_map, "1").unwrap()[1].path).unwrap();
+        assert_eq!(path.steps, [PathStep { field: 1, index: 1 }]);
+
+        assert!(query_values(&proto_map, "4.9").unwrap().is_empty());
+        assert!(query_values(&proto_map, "1.2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_query_values_string_message() {
+        // The sub-message is also valid UTF8
+        let test = [18, 5, 10, 3, 97, 98, 99];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = query_values(&proto_map, "2.1").unwrap();
+        assert_eq!(result[0].value, ProtoValue::String(String::from("abc")));
+        assert_eq!(result[0].offset, None);
+
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let proto_map = extract_protobuf_options(&test, &options).unwrap();
+        let result = query_values(&proto_map, "2.1").unwrap();
+        assert_eq!(result[0].offset, Some(2));
+    }
+}
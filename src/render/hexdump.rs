@@ -0,0 +1,335 @@
+use crate::{
+    error::SunlightError,
+    render::text::escape_bytes,
+    tags::raw::RawValue,
+    utils::strings::borrow_utf8_str,
+    view::FieldView,
+    visit::{Visit, Visitor, visit_protobuf},
+};
+use serde::Serialize;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SpanKind {
+    /**Tag varint containing the field number and wire type */
+    Tag,
+    /**Length prefix of a length prefixed value */
+    Length,
+    Value,
+    /**Start of a sub-message. Has no bytes */
+    MessageStart,
+    /**End of a sub-message. Has no bytes */
+    MessageEnd,
+}
+
+/// A labelled range of the input bytes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    /**Sub-message depth. Top level fields are 0 */
+    pub depth: usize,
+    pub kind: SpanKind,
+    pub label: String,
+}
+
+/// Number of bytes shown per line
+const BYTES_PER_LINE: usize = 16;
+
+/// Label every byte range of the provided Protobuf bytes. Length prefixed values are decoded the same way as `extract_protobuf`
+///
+/// # Example
+/// ```rust
+/// use sunlight::render::hexdump::{SpanKind, hexdump_spans};
+///
+/// let proto_bytes = [8, 150, 1];
+/// let spans = hexdump_spans(&proto_bytes).unwrap();
+/// assert_eq!(spans[0].kind, SpanKind::Tag);
+/// assert_eq!(spans[1].offset, 1);
+/// assert_eq!(spans[1].length, 2);
+/// assert_eq!(spans[1].label, "varint 150");
+/// ```
+pub fn hexdump_spans(data: &[u8]) -> Result<Vec<Span>, SunlightError> {
+    let mut labeller = Labeller {
+        spans: Vec::new(),
+        frames: Vec::new(),
+        error: None,
+    };
+    visit_protobuf(data, &mut labeller);
+    match labeller.error {
+        Some(err) => Err(err),
+        None => Ok(labeller.spans),
+    }
+}
+
+/// Render the provided Protobuf bytes as a hex dump with every byte range labelled. Sub-messages are indented
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [8, 150, 1, 18, 1, 97];
+/// let dump = sunlight::render::hexdump::hexdump_text(&proto_bytes).unwrap();
+/// let lines: Vec<&str> = dump.lines().collect();
+/// assert!(lines[1].starts_with("00000001  96 01 "));
+/// assert!(lines[1].ends_with(" varint 150"));
+/// assert!(lines[4].ends_with(" string \"a\""));
+/// ```
+pub fn hexdump_text(data: &[u8]) -> Result<String, SunlightError> {
+    let spans = hexdump_spans(data)?;
+    let mut output = String::new();
+    for span in spans {
+        let indent = "  ".repeat(span.depth);
+        let bytes = &data[span.offset..span.offset + span.length];
+        if bytes.is_empty() {
+            let _ = writeln!(
+                output,
+                "{:08x}  {:width$}{indent}{}",
+                span.offset,
+                "",
+                span.label,
+                width = BYTES_PER_LINE * 3 + 1
+            );
+            continue;
+        }
+        for (index, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let label = if index == 0 { span.label.as_str() } else { "" };
+            let _ = writeln!(
+                output,
+                "{:08x}  {:width$} {indent}{label}",
+                span.offset + index * BYTES_PER_LINE,
+                hex(chunk),
+                width = BYTES_PER_LINE * 3
+            );
+        }
+    }
+    // Labels are padded to line up. Remove trailing spaces from lines without a label
+    let mut result = String::new();
+    for line in output.lines() {
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// Render the provided Protobuf bytes as an HTML table with every byte range labelled. Rows have a class for their span kind so they can be styled
+pub fn hexdump_html(data: &[u8]) -> Result<String, SunlightError> {
+    let spans = hexdump_spans(data)?;
+    let mut output = String::from("<table class=\"sunlight-hexdump\">\n");
+    output.push_str("<tr><th>Offset</th><th>Bytes</th><th>Description</th></tr>\n");
+    for span in spans {
+        let class = match span.kind {
+            SpanKind::Tag => "tag",
+            SpanKind::Length => "length",
+            SpanKind::Value => "value",
+            SpanKind::MessageStart => "message-start",
+            SpanKind::MessageEnd => "message-end",
+        };
+        let bytes = &data[span.offset..span.offset + span.length];
+        let _ = writeln!(
+            output,
+            "<tr class=\"{class}\"><td>{:08x}</td><td>{}</td><td style=\"padding-left: {}em\">{}</td></tr>",
+            span.offset,
+            hex(bytes),
+            span.depth * 2,
+            escape_html(&span.label)
+        );
+    }
+    output.push_str("</table>\n");
+    Ok(output)
+}
+
+/// Spans of a sub-message. Kept so they can be replaced if the sub-message cannot be parsed
+struct Frame {
+    start: usize,
+    failed: bool,
+}
+
+/// Labels each field as it is parsed
+struct Labeller {
+    spans: Vec<Span>,
+    frames: Vec<Frame>,
+    error: Option<SunlightError>,
+}
+
+impl Labeller {
+    fn push(&mut self, offset: usize, length: usize, kind: SpanKind, label: String) {
+        self.spans.push(Span {
+            offset,
+            length,
+            depth: self.frames.len(),
+            kind,
+            label,
+        });
+    }
+}
+
+impl<'a> Visitor<'a> for Labeller {
+    fn enter_message(&mut self, field: &FieldView<'a>) {
+        let payload = field.as_bytes().unwrap_or_default();
+        self.push(
+            field.offset() + field.raw().len() - payload.len(),
+            0,
+            SpanKind::MessageStart,
+            format!("start of field {} message", field.field()),
+        );
+        self.frames.push(Frame {
+            start: self.spans.len() - 1,
+            failed: false,
+        });
+    }
+
+    fn field(&mut self, field: &FieldView<'a>) -> Visit {
+        let value_bytes = field.value_bytes();
+        let tag_length = field.raw().len() - value_bytes.len();
+        self.push(
+            field.offset(),
+            tag_length,
+            SpanKind::Tag,
+            format!(
+                "tag: field {}, wire type {:?}",
+                field.field(),
+                field.wire_type()
+            ),
+        );
+        let value_offset = field.offset() + tag_length;
+
+        let label = match field.value {
+            RawValue::VarInt(number) => format!("varint {number}"),
+            RawValue::Fixed64(bits) => {
+                format!("fixed64 {bits:#018x}, double {}", f64::from_bits(bits))
+            }
+            RawValue::Fixed32(bits) => {
+                format!("fixed32 {bits:#010x}, float {}", f32::from_bits(bits))
+            }
+            RawValue::Len(payload) => {
+                let prefix = value_bytes.len() - payload.len();
+                self.push(
+                    value_offset,
+                    prefix,
+                    SpanKind::Length,
+                    format!("length {}", payload.len()),
+                );
+                match borrow_utf8_str(payload) {
+                    Some(_) => {
+                        self.push(
+                            value_offset + prefix,
+                            payload.len(),
+                            SpanKind::Value,
+                            format!("string {}", escape_bytes(payload)),
+                        );
+                        return Visit::Continue;
+                    }
+                    // Labelled once the sub-message is parsed
+                    None => return Visit::Enter,
+                }
+            }
+            RawValue::Remaining(remaining) => format!(
+                "{} remaining bytes. Parsing stops at groups and unknown wire types",
+                remaining.len()
+            ),
+        };
+        self.push(value_offset, value_bytes.len(), SpanKind::Value, label);
+        Visit::Continue
+    }
+
+    fn exit_message(&mut self, field: &FieldView<'a>) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        let payload = field.as_bytes().unwrap_or_default();
+        let payload_offset = field.offset() + field.raw().len() - payload.len();
+        if frame.failed {
+            // Not a sub-message. Label the payload as bytes instead
+            self.spans.truncate(frame.start);
+            self.push(
+                payload_offset,
+                payload.len(),
+                SpanKind::Value,
+                format!("bytes {}", escape_bytes(payload)),
+            );
+            return;
+        }
+        self.push(
+            payload_offset + payload.len(),
+            0,
+            SpanKind::MessageEnd,
+            format!("end of field {} message", field.field()),
+        );
+    }
+
+    fn error(&mut self, _offset: usize, error: &SunlightError) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.failed = true,
+            None => {
+                self.error = Some(error.clone());
+            }
+        }
+    }
+}
+
+/// Format bytes as space separated hex
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpanKind, hexdump_html, hexdump_spans, hexdump_text};
+
+    #[test]
+    fn test_hexdump_spans() {
+        // Field 2 is a sub-message and field 3 is bytes that look like a broken sub-message
+        let test = [8, 1, 18, 3, 8, 150, 1, 26, 2, 10, 200];
+        let spans = hexdump_spans(&test).unwrap();
+        let kinds: Vec<SpanKind> = spans.iter().map(|span| span.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SpanKind::Tag,
+                SpanKind::Value,
+                SpanKind::Tag,
+                SpanKind::Length,
+                SpanKind::MessageStart,
+                SpanKind::Tag,
+                SpanKind::Value,
+                SpanKind::MessageEnd,
+                SpanKind::Tag,
+                SpanKind::Length,
+                SpanKind::Value,
+            ]
+        );
+        assert_eq!(spans[5].depth, 1);
+        assert_eq!(spans[5].offset, 4);
+        assert_eq!(spans[7].offset, 7);
+        assert_eq!(spans[10].label, "bytes \"\\n\\310\"");
+        assert!(hexdump_spans(&[18, 5, 1]).is_err());
+    }
+
+    #[test]
+    fn test_hexdump_text() {
+        let test = [18, 3, 8, 150, 1];
+        let dump = hexdump_text(&test).unwrap();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[2].starts_with("00000002"));
+        assert!(lines[2].ends_with("start of field 2 message"));
+        assert!(lines[3].ends_with("  tag: field 1, wire type VarInt"));
+    }
+
+    #[test]
+    fn test_hexdump_html() {
+        let test = [10, 2, 60, 98];
+        let html = hexdump_html(&test).unwrap();
+        assert!(html.contains("<tr class=\"value\"><td>00000002</td><td>3c 62</td>"));
+        assert!(html.contains("string &quot;&lt;b&quot;"));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    light::{ProtoTag, ProtoValue},
+    tags::fixed::{fixed32_json, fixed64_json},
+    utils::encoding::base64_encode_standard,
+};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Options to control how decoded values are converted to JSON
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /**How fixed 8 and 4 byte values are returned */
+    pub fixed: FixedFormat,
+}
+
+/// Fixed width values can be signed, unsigned or floats. The wire format does not say which
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FixedFormat {
+    /**Return every interpretation, such as `{"signed": 1, "unsigned": 1, "double": 5e-324}` */
+    #[default]
+    All,
+    /**Return the most plausible interpretation with its type, such as `{"type": "fixed64", "value": 1}` */
+    BestGuess,
+}
+
+/// Convert decoded Protobuf data to JSON. Fields are keyed by field number and contain the tag and value
+///
+/// # Example
+/// ```rust
+/// use sunlight::render::json::{FixedFormat, JsonOptions, proto_to_json};
+///
+/// // Field 4 is a double
+/// let proto_bytes = [8, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// let options = JsonOptions {
+///     fixed: FixedFormat::BestGuess,
+/// };
+/// let result = proto_to_json(&proto_map, &options);
+/// assert_eq!(result["1"]["value"], 1);
+/// assert_eq!(result["4"]["value"]["type"], "double");
+/// assert_eq!(result["4"]["value"]["value"], 753770588.413478);
+/// ```
+pub fn proto_to_json(proto_map: &HashMap<usize, ProtoTag>, options: &JsonOptions) -> Value {
+    let mut object = Map::new();
+    for (field, proto_tag) in proto_map {
+        let mut entry = Map::new();
+        entry.insert(
+            String::from("tag"),
+            serde_json::to_value(&proto_tag.tag).unwrap_or(Value::Null),
+        );
+        entry.insert(
+            String::from("value"),
+            value_to_json(&proto_tag.value, options),
+        );
+        object.insert(field.to_string(), Value::Object(entry));
+    }
+    Value::Object(object)
+}
+
+/// Convert a decoded value to JSON. Bytes are base64 encoded and repeated values become an array
+pub fn value_to_json(value: &ProtoValue, options: &JsonOptions) -> Value {
+    match value {
+        ProtoValue::VarInt(number) => Value::from(*number as i64),
+        ProtoValue::Fixed32(bits) => fixed32_json(*bits, &options.fixed),
+        ProtoValue::Fixed64(bits) => fixed64_json(*bits, &options.fixed),
+        ProtoValue::String(text) => Value::String(text.clone()),
+        ProtoValue::Bytes(bytes) | ProtoValue::Group(bytes) => {
+            Value::String(base64_encode_standard(bytes))
+        }
+        ProtoValue::Message(message) => proto_to_json(message, options),
+        ProtoValue::Packed(values) | ProtoValue::Repeated(values) => Value::Array(
+            values
+                .iter()
+                .map(|entry| value_to_json(entry, options))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonOptions, value_to_json};
+    use crate::light::ProtoValue;
+    use serde_json::json;
+
+    #[test]
+    fn test_value_to_json() {
+        let value = ProtoValue::Repeated(vec![
+            ProtoValue::VarInt(u64::MAX),
+            ProtoValue::Bytes(vec![0, 1, 2]),
+            ProtoValue::Packed(vec![ProtoValue::Fixed32(0)]),
+        ]);
+        let result = value_to_json(&value, &JsonOptions::default());
+        assert_eq!(
+            result,
+            json!([-1, "AAEC", [{"signed": 0, "unsigned": 0, "float": 0.0}]])
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod hexdump;
+pub mod json;
+pub mod stream;
+pub mod text;
@@ -0,0 +1,174 @@
+use crate::{
+    error::SunlightError,
+    render::json::JsonOptions,
+    tags::{
+        fixed::{fixed32_json, fixed64_json},
+        raw::RawValue,
+    },
+    utils::{encoding::base64_encode_standard, strings::borrow_utf8_str},
+    view::{FieldView, iter_fields},
+};
+use log::error;
+use serde::{
+    Serialize, Serializer,
+    ser::{self, SerializeSeq, SerializeStruct},
+};
+use std::io::Write;
+
+/// Decode Protobuf bytes straight into a serde `Serializer` without building a `HashMap` first. Memory use depends on nesting depth, not message size.
+///
+/// A message is written as a sequence of fields in wire order, each with a `tag` and `value` like `proto_to_json`.
+/// Repeated fields are written once per value instead of being grouped into an array. Length prefixed values are decoded the same way as `extract_protobuf`.
+/// A length prefixed value is written as a sub-message if all of its own fields can be parsed, otherwise it is written as bytes.
+/// An error in a more deeply nested value only turns that value into bytes. An error in the top level message stops the stream
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [8, 150, 1, 18, 3, 97, 98, 99];
+/// let mut output = Vec::new();
+/// let mut serializer = serde_json::Serializer::new(&mut output);
+/// sunlight::render::stream::stream_protobuf(&proto_bytes, &mut serializer).unwrap();
+///
+/// let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+/// assert_eq!(result[0]["value"], 150);
+/// assert_eq!(result[1]["tag"]["field"], 2);
+/// assert_eq!(result[1]["value"], "abc");
+/// ```
+pub fn stream_protobuf<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    stream_protobuf_options(data, &JsonOptions::default(), serializer)
+}
+
+/// Decode Protobuf bytes straight into a serde `Serializer` using the provided options
+pub fn stream_protobuf_options<S: Serializer>(
+    data: &[u8],
+    options: &JsonOptions,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    StreamMessage { data, options }.serialize(serializer)
+}
+
+/// Decode Protobuf bytes and write them as JSON. Nothing is buffered besides the writer
+pub fn stream_json<W: Write>(
+    data: &[u8],
+    options: &JsonOptions,
+    writer: W,
+) -> Result<(), SunlightError> {
+    let mut serializer = serde_json::Serializer::new(writer);
+    stream_protobuf_options(data, options, &mut serializer).map_err(|err| {
+        error!("[sunlight] could not stream protobuf bytes: {err:?}");
+        SunlightError::Parser
+    })
+}
+
+/// Protobuf bytes that are decoded while they are serialized. Can be embedded in other serializable types
+#[derive(Debug, Clone, Copy)]
+pub struct StreamMessage<'a> {
+    pub data: &'a [u8],
+    pub options: &'a JsonOptions,
+}
+
+impl Serialize for StreamMessage<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for field in iter_fields(self.data) {
+            let field = field.map_err(ser::Error::custom)?;
+            seq.serialize_element(&StreamField {
+                field,
+                options: self.options,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct StreamField<'a> {
+    field: FieldView<'a>,
+    options: &'a JsonOptions,
+}
+
+impl Serialize for StreamField<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entry = serializer.serialize_struct("Field", 2)?;
+        entry.serialize_field("tag", self.field.tag())?;
+        match self.field.value {
+            RawValue::VarInt(number) => entry.serialize_field("value", &(number as i64))?,
+            RawValue::Fixed64(bits) => {
+                entry.serialize_field("value", &fixed64_json(bits, &self.options.fixed))?;
+            }
+            RawValue::Fixed32(bits) => {
+                entry.serialize_field("value", &fixed32_json(bits, &self.options.fixed))?;
+            }
+            RawValue::Len(payload) => {
+                if let Some(text) = borrow_utf8_str(payload) {
+                    entry.serialize_field("value", text)?;
+                } else if iter_fields(payload).all(|field| field.is_ok()) {
+                    // Checked first so a bad sub-message is written as bytes like extract_protobuf
+                    let message = StreamMessage {
+                        data: payload,
+                        options: self.options,
+                    };
+                    entry.serialize_field("value", &message)?;
+                } else {
+                    entry.serialize_field("value", &StreamBytes(payload))?;
+                }
+            }
+            RawValue::Remaining(remaining) => {
+                entry.serialize_field("value", &StreamBytes(remaining))?;
+            }
+        }
+        entry.end()
+    }
+}
+
+/// Bytes are base64 encoded for human readable formats like JSON and written as is for binary formats
+struct StreamBytes<'a>(&'a [u8]);
+
+impl Serialize for StreamBytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&base64_encode_standard(self.0));
+        }
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stream_json, stream_protobuf};
+    use crate::render::json::JsonOptions;
+    use serde_json::json;
+
+    #[test]
+    fn test_stream_protobuf() {
+        // Field 1 is repeated, field 2 is a sub-message and field 3 is bytes that look like a broken sub-message
+        let test = [
+            8, 1, 8, 2, 18, 3, 8, 150, 1, 26, 2, 10, 200, 45, 0, 0, 192, 63,
+        ];
+        let result = stream_protobuf(&test, serde_json::value::Serializer).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 5);
+        assert_eq!(result[1]["value"], 2);
+        assert_eq!(result[2]["value"][0]["value"], 150);
+        assert_eq!(result[3]["value"], "Csg=");
+        assert_eq!(result[4]["value"]["float"], 1.5);
+        assert_eq!(
+            result[4]["tag"],
+            json!({"field": 5, "tag_byte": 45, "wire_type": "Fixed32"})
+        );
+
+        // The sub-message parses but its own sub-message is truncated
+        let test = [18, 5, 10, 3, 18, 5, 255];
+        let result = stream_protobuf(&test, serde_json::value::Serializer).unwrap();
+        assert_eq!(result[0]["value"][0]["value"], "EgX/");
+    }
+
+    #[test]
+    fn test_stream_json() {
+        let mut output = Vec::new();
+        stream_json(&[18, 1, 97], &JsonOptions::default(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"[{"tag":{"tag_byte":18,"wire_type":"Len","field":2},"value":"a"}]"#
+        );
+        assert!(stream_json(&[18, 5, 1], &JsonOptions::default(), Vec::new()).is_err());
+    }
+}
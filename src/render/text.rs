@@ -0,0 +1,206 @@
+use crate::{
+    encode::message::encode_length_value,
+    light::{ExtractOptions, ProtoTag, ProtoValue},
+    schema::typedef::{FieldType, MessageDef},
+    tags::parser::{has_group, parse_tag},
+};
+use std::{borrow::Cow, collections::HashMap, fmt::Write};
+
+/// A single value of a field and where it was found
+struct Occurrence<'a> {
+    field: usize,
+    value: &'a ProtoValue,
+    payload: Option<&'a [u8]>,
+    offset: Option<usize>,
+}
+
+/// Convert decoded Protobuf data to Protobuf text format, the same output as `protoc --decode_raw`.
+/// Values are written in the order they were decoded. Like `protoc`, length prefixed values that can be parsed as a sub-message are written as one
+///
+/// # Example
+/// ```rust
+/// use sunlight::render::text::proto_to_text;
+///
+/// let proto_bytes = [8, 150, 1, 18, 3, 97, 98, 99, 26, 3, 8, 150, 1, 45, 0, 0, 192, 63];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// assert_eq!(
+///     proto_to_text(&proto_map),
+///     "1: 150\n2: \"abc\"\n3 {\n  1: 150\n}\n5: 0x3fc00000\n"
+/// );
+/// ```
+pub fn proto_to_text(proto_map: &HashMap<usize, ProtoTag>) -> String {
+    let mut output = String::new();
+    write_message(proto_map, None, 0, &mut output);
+    output
+}
+
+/// Convert decoded Protobuf data to Protobuf text format. Fields in the definition use their name instead of the field number
+pub fn proto_to_text_with_schema(
+    proto_map: &HashMap<usize, ProtoTag>,
+    message: &MessageDef,
+) -> String {
+    let mut output = String::new();
+    write_message(proto_map, Some(message), 0, &mut output);
+    output
+}
+
+/// Write each field of a message in wire order
+fn write_message(
+    proto_map: &HashMap<usize, ProtoTag>,
+    message: Option<&MessageDef>,
+    depth: usize,
+    output: &mut String,
+) {
+    let mut values: Vec<Occurrence<'_>> = proto_map
+        .values()
+        .flat_map(|proto_tag| {
+            proto_tag
+                .value
+                .values()
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let record = proto_tag.records.get(index);
+                    Occurrence {
+                        field: proto_tag.tag.field,
+                        value,
+                        payload: record.and_then(|entry| entry.payload.as_deref()),
+                        offset: record.map(|entry| entry.offset),
+                    }
+                })
+        })
+        .collect();
+    values.sort_by_key(|entry| (entry.offset.unwrap_or(usize::MAX), entry.field));
+
+    let indent = "  ".repeat(depth);
+    for entry in values {
+        let def = message.and_then(|value| value.fields.get(&entry.field));
+        let name = def.map_or_else(|| entry.field.to_string(), |value| value.name.clone());
+        let nested = def.and_then(|value| match &value.field_type {
+            FieldType::Message(sub) => Some(sub),
+            _ => None,
+        });
+
+        let sub = match entry.value {
+            ProtoValue::Message(sub) if !has_group(sub) => Some(Cow::Borrowed(sub)),
+            // Strings and bytes declared in the definition are never sub-messages
+            ProtoValue::String(_) | ProtoValue::Bytes(_)
+                if !def.is_some_and(|value| {
+                    matches!(value.field_type, FieldType::String | FieldType::Bytes)
+                }) =>
+            {
+                length_payload(&entry)
+                    .and_then(|payload| sub_message(&payload))
+                    .map(Cow::Owned)
+            }
+            _ => None,
+        };
+        if let Some(sub) = sub {
+            let _ = writeln!(output, "{indent}{name} {{");
+            write_message(&sub, nested, depth + 1, output);
+            let _ = writeln!(output, "{indent}}}");
+            continue;
+        }
+
+        let value = match entry.value {
+            ProtoValue::VarInt(number) => number.to_string(),
+            ProtoValue::Fixed64(bits) => format!("0x{bits:016x}"),
+            ProtoValue::Fixed32(bits) => format!("0x{bits:08x}"),
+            ProtoValue::Group(remaining) => escape_bytes(remaining),
+            _ => escape_bytes(&length_payload(&entry).unwrap_or_default()),
+        };
+        let _ = writeln!(output, "{indent}{name}: {value}");
+    }
+}
+
+/// Get the payload of a length prefixed value
+fn length_payload(entry: &Occurrence<'_>) -> Option<Vec<u8>> {
+    match entry.payload {
+        Some(payload) => Some(payload.to_vec()),
+        None => encode_length_value(entry.value),
+    }
+}
+
+/// Parse a payload as a sub-message. Empty payloads and payloads ending in a group or unknown wire type are not sub-messages
+fn sub_message(payload: &[u8]) -> Option<HashMap<usize, ProtoTag>> {
+    if payload.is_empty() {
+        return None;
+    }
+    let (_, sub) = parse_tag(payload, 0, &ExtractOptions::default()).ok()?;
+    if has_group(&sub) {
+        return None;
+    }
+    Some(sub)
+}
+
+/// Quote and escape bytes the same way as `protoc`. Bytes outside of printable ASCII are written as octal escapes
+pub(crate) fn escape_bytes(data: &[u8]) -> String {
+    let mut output = String::from("\"");
+    for byte in data {
+        match byte {
+            b'\n' => output.push_str("\\n"),
+            b'\r' => output.push_str("\\r"),
+            b'\t' => output.push_str("\\t"),
+            b'"' => output.push_str("\\\""),
+            b'\'' => output.push_str("\\'"),
+            b'\\' => output.push_str("\\\\"),
+            0x20..=0x7e => output.push(*byte as char),
+            _ => {
+                let _ = write!(output, "\\{byte:03o}");
+            }
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_bytes, proto_to_text, proto_to_text_with_schema};
+    use crate::{
+        light::{ExtractOptions, extract_protobuf, extract_protobuf_options},
+        schema::typedef::{FieldDef, FieldType, MessageDef},
+    };
+
+    #[test]
+    fn test_proto_to_text() {
+        // Field 1 is interleaved with field 2. Field 3 is bytes and field 4 is a double
+        let test = [
+            8, 1, 18, 5, 10, 3, 97, 98, 99, 8, 2, 26, 2, 255, 0, 33, 217, 236, 52, 46, 208, 118,
+            198, 65,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        assert_eq!(
+            proto_to_text(&proto_map),
+            "1: 1\n2 {\n  1: \"abc\"\n}\n1: 2\n3: \"\\377\\000\"\n4: 0x41c676d02e34ecd9\n"
+        );
+    }
+
+    #[test]
+    fn test_proto_to_text_with_schema() {
+        let mut name = MessageDef::new("Name");
+        name.add_field(FieldDef::new(1, "first", FieldType::String));
+        let mut message = MessageDef::new("Person");
+        message.add_field(FieldDef::new(1, "id", FieldType::Int64));
+        message.add_field(FieldDef::new(2, "name", FieldType::Message(name)));
+        message.add_field(FieldDef::new(3, "note", FieldType::String));
+
+        // Field 3 could be parsed as a sub-message but is declared as a string
+        let test = [8, 1, 18, 5, 10, 3, 97, 98, 99, 26, 2, 8, 1, 32, 5];
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let proto_map = extract_protobuf_options(&test, &options).unwrap();
+        assert_eq!(
+            proto_to_text_with_schema(&proto_map, &message),
+            "id: 1\nname {\n  first: \"abc\"\n}\nnote: \"\\010\\001\"\n4: 5\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_bytes() {
+        assert_eq!(escape_bytes(b"a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+        assert_eq!(escape_bytes("é".as_bytes()), "\"\\303\\251\"");
+    }
+}
@@ -0,0 +1,318 @@
+use crate::{
+    light::{ProtoTag, ProtoValue},
+    schema::typedef::{
+        FieldDef, FieldType, MessageDef, field_name, merge_fields, message_name, observe_type,
+    },
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Collects observations from many decoded messages of the same type and merges them into one typedef
+///
+/// # Example
+/// ```rust
+/// let mut accumulator = sunlight::schema::accumulator::TypedefAccumulator::new();
+/// for sample in [[8, 1, 18, 1, 97].as_slice(), [8, 2].as_slice()] {
+///     let proto_map = sunlight::light::extract_protobuf(sample).unwrap();
+///     accumulator.add(&proto_map);
+/// }
+/// let typedef = accumulator.finish();
+/// assert_eq!(typedef.fields[1].path, "2");
+/// assert_eq!(typedef.fields[1].presence, 0.5);
+/// ```
+#[derive(Debug, Default)]
+pub struct TypedefAccumulator {
+    root: MessageStats,
+}
+
+/// The merged typedef along with a report for every field path
+#[derive(Debug, Serialize)]
+pub struct InferredTypedef {
+    pub message: MessageDef,
+    /**Number of messages that were added */
+    pub samples: usize,
+    pub fields: Vec<FieldReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldReport {
+    /**Field numbers from the root message separated by `.` */
+    pub path: String,
+    pub field_type: String,
+    pub repeated: bool,
+    /**Total number of values seen */
+    pub count: usize,
+    /**Fraction of parent messages that contained the field */
+    pub presence: f64,
+    /**Fraction of values that matched the most common type */
+    pub confidence: f64,
+    /**Number of values seen for each type */
+    pub observed: BTreeMap<String, usize>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct MessageStats {
+    /**Number of times the message was seen */
+    count: usize,
+    fields: BTreeMap<usize, FieldStats>,
+}
+
+#[derive(Debug, Default)]
+struct FieldStats {
+    /**Number of parent messages containing the field */
+    present: usize,
+    /**Total number of values */
+    count: usize,
+    /**Most values seen in a single parent message */
+    max_repeat: usize,
+    observed: BTreeMap<String, usize>,
+    min: Option<f64>,
+    max: Option<f64>,
+    nested: Option<MessageStats>,
+}
+
+impl TypedefAccumulator {
+    pub fn new() -> TypedefAccumulator {
+        TypedefAccumulator::default()
+    }
+
+    /// Add observations from Protobuf data returned by `extract_protobuf`
+    pub fn add(&mut self, proto_map: &HashMap<usize, ProtoTag>) {
+        self.root.add(proto_map);
+    }
+
+    /// Number of messages added so far
+    pub fn samples(&self) -> usize {
+        self.root.count
+    }
+
+    /// Merge all observations into a typedef. The accumulator can keep collecting afterwards
+    pub fn finish(&self) -> InferredTypedef {
+        let mut fields = Vec::new();
+        let message = self.root.build("Message", "", &mut fields);
+        InferredTypedef {
+            message,
+            samples: self.root.count,
+            fields,
+        }
+    }
+}
+
+impl MessageStats {
+    /// Record one occurrence of the message
+    fn add(&mut self, proto_map: &HashMap<usize, ProtoTag>) {
+        self.count += 1;
+        for (number, proto_tag) in proto_map {
+            let number = *number;
+            let values = proto_tag.value.values();
+
+            let stats = self.fields.entry(number).or_default();
+            stats.present += 1;
+            stats.count += values.len();
+            stats.max_repeat = stats.max_repeat.max(values.len());
+
+            for entry in values {
+                if let ProtoValue::Message(sub) = entry {
+                    *stats.observed.entry(String::from("message")).or_default() += 1;
+                    stats
+                        .nested
+                        .get_or_insert_with(MessageStats::default)
+                        .add(sub);
+                    continue;
+                }
+
+                let field_type = match observe_type(number, entry) {
+                    Some(result) => result,
+                    None => continue,
+                };
+                if let Some(number) = numeric_value(&field_type, entry) {
+                    stats.min = Some(stats.min.map_or(number, |min| min.min(number)));
+                    stats.max = Some(stats.max.map_or(number, |max| max.max(number)));
+                }
+                *stats
+                    .observed
+                    .entry(field_type.proto_name().to_string())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// Build the message definition and add a report for each field
+    fn build(&self, name: &str, parent: &str, reports: &mut Vec<FieldReport>) -> MessageDef {
+        let mut message = MessageDef {
+            name: name.to_string(),
+            fields: BTreeMap::new(),
+        };
+
+        for (number, stats) in &self.fields {
+            let path = if parent.is_empty() {
+                number.to_string()
+            } else {
+                format!("{parent}.{number}")
+            };
+
+            // Most common type first, so it wins when types cannot be merged
+            let mut observed: Vec<(&String, &usize)> = stats.observed.iter().collect();
+            observed.sort_by(|first, second| second.1.cmp(first.1));
+
+            let mut nested_reports = Vec::new();
+            let mut field: Option<FieldDef> = None;
+            for (label, _) in &observed {
+                let field_type = match (label.as_str(), &stats.nested) {
+                    ("message", Some(nested)) => FieldType::Message(nested.build(
+                        &message_name(*number),
+                        &path,
+                        &mut nested_reports,
+                    )),
+                    _ => match FieldType::from_proto_name(label) {
+                        Some(result) => result,
+                        None => continue,
+                    },
+                };
+                let entry = FieldDef {
+                    number: *number,
+                    name: field_name(*number),
+                    field_type,
+                    repeated: stats.max_repeat > 1,
+                    conflicts: Vec::new(),
+                };
+                field = Some(match field {
+                    Some(existing) => merge_fields(existing, entry),
+                    None => entry,
+                });
+            }
+
+            let field = match field {
+                Some(result) => result,
+                None => continue,
+            };
+
+            let most_common = observed.first().map_or(0, |(_, count)| **count);
+            reports.push(FieldReport {
+                path,
+                field_type: field.field_type.proto_name().to_string(),
+                repeated: field.repeated,
+                count: stats.count,
+                presence: stats.present as f64 / self.count.max(1) as f64,
+                confidence: most_common as f64 / stats.count.max(1) as f64,
+                observed: stats.observed.clone(),
+                min: stats.min,
+                max: stats.max,
+                conflicts: field.conflicts.clone(),
+            });
+            reports.append(&mut nested_reports);
+            message.fields.insert(*number, field);
+        }
+
+        message
+    }
+}
+
+/// Get the number used for min and max tracking
+fn numeric_value(field_type: &FieldType, value: &ProtoValue) -> Option<f64> {
+    let number = match (field_type, value) {
+        (FieldType::Uint64 | FieldType::Uint32, ProtoValue::VarInt(number)) => *number as f64,
+        (
+            FieldType::Int64
+            | FieldType::Int32
+            | FieldType::Sint32
+            | FieldType::Sint64
+            | FieldType::Bool,
+            ProtoValue::VarInt(number),
+        ) => *number as i64 as f64,
+        (FieldType::Double, ProtoValue::Fixed64(bits)) => f64::from_bits(*bits),
+        (FieldType::Fixed64, ProtoValue::Fixed64(bits)) => *bits as f64,
+        (FieldType::Sfixed64, ProtoValue::Fixed64(bits)) => *bits as i64 as f64,
+        (FieldType::Float, ProtoValue::Fixed32(bits)) => f32::from_bits(*bits) as f64,
+        (FieldType::Fixed32, ProtoValue::Fixed32(bits)) => *bits as f64,
+        (FieldType::Sfixed32, ProtoValue::Fixed32(bits)) => *bits as i32 as f64,
+        _ => return None,
+    };
+    Some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedefAccumulator;
+    use crate::{light::extract_protobuf, schema::typedef::FieldType};
+
+    #[test]
+    fn test_typedef_accumulator() {
+        let samples = [
+            vec![
+                16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46,
+                100, 117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98,
+                114, 111, 119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56,
+                88, 1, 96, 1, 0, 0, 0,
+            ],
+            vec![
+                16, 1, 24, 0, 33, 19, 41, 57, 157, 203, 118, 198, 65, 50, 25, 99, 111, 109, 46,
+                109, 105, 99, 114, 111, 115, 111, 102, 116, 46, 97, 117, 116, 111, 117, 112, 100,
+                97, 116, 101, 50, 74, 4, 52, 46, 55, 54, 82, 13, 52, 46, 55, 54, 46, 50, 52, 49,
+                48, 49, 51, 56, 55, 88, 1, 96, 1, 0, 0, 0,
+            ],
+        ];
+
+        let mut accumulator = TypedefAccumulator::new();
+        for sample in samples {
+            accumulator.add(&extract_protobuf(&sample).unwrap());
+        }
+        assert_eq!(accumulator.samples(), 2);
+
+        let result = accumulator.finish();
+        assert_eq!(result.message.fields[&4].field_type, FieldType::Double);
+
+        let report = result
+            .fields
+            .iter()
+            .find(|field| field.path == "3")
+            .unwrap();
+        assert_eq!(report.min, Some(0.0));
+        assert_eq!(report.max, Some(1.0));
+        assert_eq!(report.presence, 1.0);
+
+        let report = result
+            .fields
+            .iter()
+            .find(|field| field.path == "4")
+            .unwrap();
+        assert_eq!(report.min, Some(753768250.446566));
+        assert_eq!(report.max, Some(753770588.413478));
+        assert_eq!(report.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_typedef_accumulator_conflict() {
+        // Field 1 is a string in the first sample and a sub-message in the other two
+        let samples = [
+            vec![10, 3, 97, 98, 99],
+            vec![10, 3, 8, 150, 1],
+            vec![10, 5, 8, 150, 1, 16, 1],
+        ];
+
+        let mut accumulator = TypedefAccumulator::new();
+        for sample in samples {
+            accumulator.add(&extract_protobuf(&sample).unwrap());
+        }
+        let result = accumulator.finish();
+
+        assert_eq!(result.message.fields[&1].field_type, FieldType::Bytes);
+        assert_eq!(result.fields[0].path, "1");
+        assert_eq!(
+            result.fields[0].conflicts,
+            vec!["observed message and string"]
+        );
+        assert!((result.fields[0].confidence - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        let report = result
+            .fields
+            .iter()
+            .find(|field| field.path == "1.2")
+            .unwrap();
+        assert_eq!(report.presence, 0.5);
+        assert_eq!(report.count, 1);
+    }
+}
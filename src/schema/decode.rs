@@ -0,0 +1,391 @@
+use crate::{
+    encode::message::encode_length_value,
+    error::SunlightError,
+    light::{ExtractOptions, ProtoTag, ProtoValue, WireType},
+    render::json::{JsonOptions, value_to_json},
+    schema::{
+        registry::Schema,
+        typedef::{FieldDef, FieldType, MessageDef},
+        wellknown::{float_value, well_known_value},
+    },
+    tags::{
+        length::length_value,
+        raw::{RawValue, parse_raw_fields},
+        var::{parse_varint, zigzag},
+    },
+    utils::encoding::base64_encode_standard,
+};
+use log::error;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Decode Protobuf bytes using a message definition. Fields in the definition are keyed by name and converted to their declared type.
+/// Well-known types are returned as their canonical JSON form. Fields missing from the definition are keyed by field number and decoded like `extract_protobuf`,
+/// so a partial definition can be used to provide type hints
+///
+/// # Example
+/// ```rust
+/// use sunlight::schema::{
+///     decode::decode_with_schema,
+///     registry::Schema,
+///     typedef::{FieldDef, FieldType, MessageDef, WellKnownType},
+/// };
+///
+/// let mut message = MessageDef::new("Event");
+/// message.add_field(FieldDef::new(1, "created", FieldType::WellKnown(WellKnownType::Timestamp)));
+///
+/// // Field 1 is a Timestamp with 1732406400 seconds, field 2 is not in the definition
+/// let proto_bytes = [10, 6, 8, 128, 217, 137, 186, 6, 16, 1];
+/// let result = decode_with_schema(&proto_bytes, &message, &Schema::new()).unwrap();
+/// assert_eq!(result["created"], "2024-11-24T00:00:00Z");
+/// assert_eq!(result["2"], 1);
+/// ```
+pub fn decode_with_schema(
+    data: &[u8],
+    message: &MessageDef,
+    schema: &Schema,
+) -> Result<Value, SunlightError> {
+    decode_message(data, message, schema).map(Value::Object)
+}
+
+/// Decode a message into a JSON object
+pub(crate) fn decode_message(
+    data: &[u8],
+    message: &MessageDef,
+    schema: &Schema,
+) -> Result<Map<String, Value>, SunlightError> {
+    let fields = match parse_raw_fields(data) {
+        Ok((_, result)) => result,
+        Err(err) => {
+            error!(
+                "[sunlight] could not parse protobuf bytes for {}: {err:?}",
+                message.name
+            );
+            return Err(SunlightError::Parser);
+        }
+    };
+
+    let mut object = Map::new();
+    for field in fields {
+        let definition = message.fields.get(&field.tag.field);
+        let (key, values, repeated) = match definition {
+            Some(def) => (
+                def.name.clone(),
+                typed_values(def, &field.value, schema),
+                def.repeated,
+            ),
+            None => (
+                field.tag.field.to_string(),
+                vec![untyped_value(&field.value)],
+                false,
+            ),
+        };
+
+        for value in values {
+            match object.get_mut(&key) {
+                Some(Value::Array(existing)) if repeated || definition.is_none() => {
+                    existing.push(value);
+                }
+                // Unknown fields become an array the second time they are seen, like `extract_protobuf`
+                Some(existing) if definition.is_none() => {
+                    *existing = Value::Array(vec![existing.take(), value]);
+                }
+                _ if repeated => {
+                    object.insert(key.clone(), Value::Array(vec![value]));
+                }
+                // Last value wins for non-repeated fields
+                _ => {
+                    object.insert(key.clone(), value);
+                }
+            }
+        }
+    }
+
+    Ok(object)
+}
+
+/// Convert a value to the declared field type. Values that do not match the declared type are decoded without the definition
+fn typed_values(def: &FieldDef, value: &RawValue<'_>, schema: &Schema) -> Vec<Value> {
+    let typed = match value {
+        RawValue::VarInt(number) => varint_value(&def.field_type, *number),
+        RawValue::Fixed64(number) => fixed64_value(&def.field_type, *number),
+        RawValue::Fixed32(number) => fixed32_value(&def.field_type, *number),
+        RawValue::Len(payload) => return length_values(def, payload, schema),
+        RawValue::Remaining(_) => None,
+    };
+
+    vec![typed.unwrap_or_else(|| untyped_value(value))]
+}
+
+/// Convert a length prefixed value. Repeated numeric fields may be packed
+fn length_values(def: &FieldDef, payload: &[u8], schema: &Schema) -> Vec<Value> {
+    let value = match &def.field_type {
+        FieldType::String => Some(Value::String(String::from_utf8_lossy(payload).into_owned())),
+        FieldType::Bytes => Some(Value::String(base64_encode_standard(payload))),
+        FieldType::Message(message) => decode_message(payload, message, schema)
+            .ok()
+            .map(Value::Object),
+        FieldType::WellKnown(well_known) => well_known_value(*well_known, payload, schema),
+        field_type => match packed_values(field_type, payload) {
+            Some(values) => return values,
+            None => None,
+        },
+    };
+
+    let value = value.unwrap_or_else(|| untyped_value(&RawValue::Len(payload)));
+    vec![value]
+}
+
+/// Decode packed numeric values
+fn packed_values(field_type: &FieldType, payload: &[u8]) -> Option<Vec<Value>> {
+    unpack(&field_type.wire_type(), payload)?
+        .iter()
+        .map(|value| match value {
+            ProtoValue::VarInt(number) => varint_value(field_type, *number),
+            ProtoValue::Fixed64(number) => fixed64_value(field_type, *number),
+            ProtoValue::Fixed32(number) => fixed32_value(field_type, *number),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split a packed payload into scalar values of the provided wire type
+fn unpack(wire_type: &WireType, payload: &[u8]) -> Option<Vec<ProtoValue>> {
+    let mut values = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let value = match wire_type {
+            WireType::VarInt => {
+                let (input, number) = parse_varint(remaining).ok()?;
+                remaining = input;
+                ProtoValue::VarInt(number)
+            }
+            WireType::Fixed64 => {
+                let bytes = remaining.get(..8)?;
+                remaining = &remaining[8..];
+                ProtoValue::Fixed64(u64::from_le_bytes(bytes.try_into().ok()?))
+            }
+            WireType::Fixed32 => {
+                let bytes = remaining.get(..4)?;
+                remaining = &remaining[4..];
+                ProtoValue::Fixed32(u32::from_le_bytes(bytes.try_into().ok()?))
+            }
+            _ => return None,
+        };
+        values.push(value);
+    }
+    Some(values)
+}
+
+/// Convert fields of a decoded message that are declared as repeated scalars to `ProtoValue::Packed`.
+/// Without a definition the parser returns packed values as strings, bytes or sub-messages. Use `ExtractOptions::lossless` when decoding so the exact payload is unpacked
+///
+/// # Example
+/// ```rust
+/// use sunlight::{
+///     light::{ExtractOptions, ProtoValue, extract_protobuf_options},
+///     schema::{
+///         decode::unpack_with_schema,
+///         typedef::{FieldDef, FieldType, MessageDef},
+///     },
+/// };
+///
+/// let mut message = MessageDef::new("Event");
+/// let mut ids = FieldDef::new(1, "ids", FieldType::Uint32);
+/// ids.repeated = true;
+/// message.add_field(ids);
+///
+/// let proto_bytes = [10, 4, 1, 2, 150, 1];
+/// let options = ExtractOptions {
+///     lossless: true,
+///     ..Default::default()
+/// };
+/// let mut proto_map = extract_protobuf_options(&proto_bytes, &options).unwrap();
+/// unpack_with_schema(&mut proto_map, &message);
+/// assert_eq!(
+///     proto_map[&1].value,
+///     ProtoValue::Packed(vec![ProtoValue::VarInt(1), ProtoValue::VarInt(2), ProtoValue::VarInt(150)])
+/// );
+/// ```
+pub fn unpack_with_schema(proto_map: &mut HashMap<usize, ProtoTag>, message: &MessageDef) {
+    for (field, proto_tag) in proto_map.iter_mut() {
+        let Some(def) = message.fields.get(field) else {
+            continue;
+        };
+        let wire_type = def.field_type.wire_type();
+        for (index, value) in proto_tag.value.values_mut().iter_mut().enumerate() {
+            if let (FieldType::Message(sub), ProtoValue::Message(sub_map)) =
+                (&def.field_type, &mut *value)
+            {
+                unpack_with_schema(sub_map, sub);
+                continue;
+            }
+            if !def.repeated || wire_type == WireType::Len || value.wire_type() != WireType::Len {
+                continue;
+            }
+
+            let record = proto_tag.records.get(index).filter(|entry| !entry.edited);
+            let payload = match record.and_then(|entry| entry.payload.clone()) {
+                Some(result) => Some(result),
+                None => encode_length_value(value),
+            };
+            if let Some(values) = payload.and_then(|result| unpack(&wire_type, &result)) {
+                *value = ProtoValue::Packed(values);
+            }
+        }
+    }
+}
+
+/// Convert a varint to the declared type
+fn varint_value(field_type: &FieldType, number: u64) -> Option<Value> {
+    let value = match field_type {
+        FieldType::Int64 => Value::from(number as i64),
+        FieldType::Uint64 => Value::from(number),
+        FieldType::Int32 => Value::from(number as i32),
+        FieldType::Uint32 => Value::from(number as u32),
+        FieldType::Sint32 | FieldType::Sint64 => Value::from(zigzag(number)),
+        FieldType::Bool => Value::Bool(number != 0),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Convert a fixed 8 byte value to the declared type
+fn fixed64_value(field_type: &FieldType, number: u64) -> Option<Value> {
+    let value = match field_type {
+        FieldType::Double => float_value(f64::from_bits(number)),
+        FieldType::Fixed64 => Value::from(number),
+        FieldType::Sfixed64 => Value::from(number as i64),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Convert a fixed 4 byte value to the declared type
+fn fixed32_value(field_type: &FieldType, number: u32) -> Option<Value> {
+    let value = match field_type {
+        FieldType::Float => float_value(f32::from_bits(number) as f64),
+        FieldType::Fixed32 => Value::from(number),
+        FieldType::Sfixed32 => Value::from(number as i32),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Decode a value without a definition, the same way as `extract_protobuf`
+fn untyped_value(value: &RawValue<'_>) -> Value {
+    let value = match value {
+        RawValue::VarInt(number) => ProtoValue::VarInt(*number),
+        RawValue::Fixed64(number) => ProtoValue::Fixed64(*number),
+        RawValue::Fixed32(number) => ProtoValue::Fixed32(*number),
+        RawValue::Len(payload) => length_value(payload, 0, &ExtractOptions::default()),
+        RawValue::Remaining(remaining) => ProtoValue::Group(remaining.to_vec()),
+    };
+    value_to_json(&value, &JsonOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_with_schema, unpack_with_schema};
+    use crate::{
+        encode::message::encode_protobuf,
+        light::{ExtractOptions, ProtoValue, extract_protobuf, extract_protobuf_options},
+        schema::{
+            registry::Schema,
+            typedef::{FieldDef, FieldType, MessageDef},
+        },
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_decode_with_schema() {
+        let test = [
+            16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46, 100,
+            117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98, 114, 111,
+            119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
+        ];
+        let mut message = MessageDef::new("AppLaunch");
+        message.add_field(FieldDef::new(2, "launched", FieldType::Bool));
+        message.add_field(FieldDef::new(4, "time", FieldType::Double));
+        message.add_field(FieldDef::new(6, "bundle_id", FieldType::String));
+
+        let result = decode_with_schema(&test, &message, &Schema::new()).unwrap();
+        assert_eq!(result["launched"], true);
+        assert_eq!(result["time"], 753770588.413478);
+        assert_eq!(result["bundle_id"], "com.duckduckgo.macos.browser");
+        assert_eq!(result["9"], "1.114.0");
+    }
+
+    #[test]
+    fn test_decode_with_schema_repeated() {
+        // Field 1 is packed, field 2 is not. Field 3 is a nested message
+        let test = [10, 3, 1, 2, 3, 16, 3, 16, 5, 26, 2, 8, 1];
+        let mut nested = MessageDef::new("Nested");
+        nested.add_field(FieldDef::new(1, "value", FieldType::Sint32));
+
+        let mut message = MessageDef::new("Test");
+        let mut packed = FieldDef::new(1, "packed", FieldType::Int32);
+        packed.repeated = true;
+        message.add_field(packed);
+        let mut unpacked = FieldDef::new(2, "unpacked", FieldType::Uint64);
+        unpacked.repeated = true;
+        message.add_field(unpacked);
+        message.add_field(FieldDef::new(3, "nested", FieldType::Message(nested)));
+
+        let result = decode_with_schema(&test, &message, &Schema::new()).unwrap();
+        assert_eq!(
+            result,
+            json!({"packed": [1, 2, 3], "unpacked": [3, 5], "nested": {"value": -1}})
+        );
+    }
+
+    #[test]
+    fn test_unpack_with_schema() {
+        // Field 2 is a sub-message with packed floats in field 1. Field 3 is a single string
+        let test = [18, 6, 10, 4, 0, 0, 192, 63, 26, 1, 97];
+        let mut nested = MessageDef::new("Nested");
+        let mut values = FieldDef::new(1, "values", FieldType::Float);
+        values.repeated = true;
+        nested.add_field(values);
+        let mut message = MessageDef::new("Event");
+        message.add_field(FieldDef::new(2, "nested", FieldType::Message(nested)));
+        message.add_field(FieldDef::new(3, "name", FieldType::String));
+
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let mut proto_map = extract_protobuf_options(&test, &options).unwrap();
+        unpack_with_schema(&mut proto_map, &message);
+
+        let sub = proto_map[&2].value.as_message().unwrap();
+        assert_eq!(
+            sub[&1].value,
+            ProtoValue::Packed(vec![ProtoValue::Fixed32(1.5_f32.to_bits())])
+        );
+        assert_eq!(proto_map[&3].value, ProtoValue::String(String::from("a")));
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), test);
+    }
+
+    #[test]
+    fn test_unpack_with_schema_trailing_zero() {
+        // The packed values are valid UTF8 with a trailing NULL character
+        let test = [10, 3, 1, 2, 0];
+        let mut message = MessageDef::new("Event");
+        let mut ids = FieldDef::new(1, "ids", FieldType::Uint32);
+        ids.repeated = true;
+        message.add_field(ids);
+
+        let mut proto_map = extract_protobuf(&test).unwrap();
+        unpack_with_schema(&mut proto_map, &message);
+        assert_eq!(
+            proto_map[&1].value,
+            ProtoValue::Packed(vec![
+                ProtoValue::VarInt(1),
+                ProtoValue::VarInt(2),
+                ProtoValue::VarInt(0)
+            ])
+        );
+        assert_eq!(encode_protobuf(&proto_map).unwrap(), test);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod accumulator;
+pub mod decode;
+pub mod proto;
+pub mod registry;
+pub mod typedef;
+mod wellknown;
@@ -0,0 +1,174 @@
+use crate::schema::typedef::{FieldType, MessageDef};
+use std::{collections::BTreeSet, fmt::Write};
+
+/// Generate a best-guess `.proto` file from a `MessageDef`. Nested messages are declared inside their parent message
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [10, 10, 112, 114, 111, 100, 117, 99, 116, 105, 111, 110, 16, 1];
+/// let proto_map = sunlight::light::extract_protobuf(&proto_bytes).unwrap();
+/// let message = sunlight::schema::typedef::infer_typedef(&proto_map);
+/// let proto = sunlight::schema::proto::generate_proto(&message);
+/// assert!(proto.contains("optional string field_1 = 1;"));
+/// ```
+pub fn generate_proto(message: &MessageDef) -> String {
+    let mut proto = String::from("syntax = \"proto2\";\n\n");
+
+    let mut imports = BTreeSet::new();
+    collect_imports(message, &mut imports);
+    for import in &imports {
+        let _ = writeln!(proto, "import \"{import}\";");
+    }
+    if !imports.is_empty() {
+        proto.push('\n');
+    }
+
+    write_message(message, 0, &mut proto);
+    proto
+}
+
+/// Collect the files needed for any well-known types used by the message
+fn collect_imports(message: &MessageDef, imports: &mut BTreeSet<&'static str>) {
+    for field in message.fields.values() {
+        match &field.field_type {
+            FieldType::WellKnown(well_known) => {
+                imports.insert(well_known.import_path());
+            }
+            FieldType::Message(nested) => collect_imports(nested, imports),
+            _ => {}
+        }
+    }
+}
+
+/// Write a message declaration and any nested messages it uses
+fn write_message(message: &MessageDef, depth: usize, proto: &mut String) {
+    let indent = "  ".repeat(depth);
+    let _ = writeln!(proto, "{indent}message {} {{", message.name);
+
+    for field in message.fields.values() {
+        if !valid_field_number(field.number) {
+            let _ = writeln!(
+                proto,
+                "{indent}  // field {} skipped: not a valid field number",
+                field.number
+            );
+            continue;
+        }
+
+        let label = if field.repeated {
+            "repeated"
+        } else {
+            "optional"
+        };
+        let _ = write!(
+            proto,
+            "{indent}  {label} {} {} = {};",
+            field.field_type.proto_name(),
+            field.name,
+            field.number
+        );
+        if !field.conflicts.is_empty() {
+            let _ = write!(proto, " // conflict: {}", field.conflicts.join("; "));
+        }
+        proto.push('\n');
+    }
+
+    for field in message.fields.values() {
+        if let FieldType::Message(nested) = &field.field_type
+            && valid_field_number(field.number)
+        {
+            proto.push('\n');
+            write_message(nested, depth + 1, proto);
+        }
+    }
+
+    let _ = writeln!(proto, "{indent}}}");
+}
+
+/// protoc rejects field 0, numbers above 2^29 - 1 and the reserved range 19000 to 19999
+fn valid_field_number(number: usize) -> bool {
+    let max = 536870911;
+    let reserved = 19000..=19999;
+    number != 0 && number <= max && !reserved.contains(&number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_proto, valid_field_number};
+    use crate::{
+        light::extract_protobuf,
+        schema::typedef::{FieldDef, FieldType, MessageDef, WellKnownType, infer_typedef},
+    };
+    use std::{fs::read, path::PathBuf};
+
+    #[test]
+    fn test_generate_proto() {
+        let test = [
+            16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46, 100,
+            117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98, 114, 111,
+            119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
+            0, 0, 0,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = generate_proto(&infer_typedef(&proto_map));
+        assert_eq!(
+            result,
+            "syntax = \"proto2\";\n\nmessage Message {\n  // field 0 skipped: not a valid field number\n  optional uint64 field_2 = 2;\n  optional uint64 field_3 = 3;\n  optional double field_4 = 4;\n  optional string field_6 = 6;\n  optional string field_9 = 9;\n  optional string field_10 = 10;\n  optional uint64 field_11 = 11;\n  optional uint64 field_12 = 12;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_proto_nested() {
+        let test = [
+            10, 3, 8, 150, 1, 10, 3, 97, 98, 99, 18, 8, 8, 150, 1, 18, 3, 97, 98, 99,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = generate_proto(&infer_typedef(&proto_map));
+        assert_eq!(
+            result,
+            "syntax = \"proto2\";\n\nmessage Message {\n  repeated bytes field_1 = 1; // conflict: observed message and string\n  optional Field2 field_2 = 2;\n\n  message Field2 {\n    optional uint64 field_1 = 1;\n    optional string field_2 = 2;\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_proto_blackboxprotobuf() {
+        let mut test_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_path.push("tests/test_data/blackboxprotobuf/test_message.out");
+        let data = read(test_path.to_str().unwrap()).unwrap();
+
+        let proto_map = extract_protobuf(&data).unwrap();
+        let result = generate_proto(&infer_typedef(&proto_map));
+        assert!(result.contains("optional double field_1 = 1;"));
+        assert!(result.contains("optional sfixed32 field_1024 = 1024;"));
+        assert!(result.contains("optional Field32768 field_32768 = 32768;"));
+        assert!(result.contains("  message Field32768 {\n    optional string field_2 = 2;"));
+    }
+
+    #[test]
+    fn test_generate_proto_well_known() {
+        let mut message = MessageDef::new("Event");
+        message.add_field(FieldDef::new(
+            1,
+            "created",
+            FieldType::WellKnown(WellKnownType::Timestamp),
+        ));
+        message.add_field(FieldDef::new(
+            2,
+            "name",
+            FieldType::WellKnown(WellKnownType::StringValue),
+        ));
+
+        let result = generate_proto(&message);
+        assert_eq!(
+            result,
+            "syntax = \"proto2\";\n\nimport \"google/protobuf/timestamp.proto\";\nimport \"google/protobuf/wrappers.proto\";\n\nmessage Event {\n  optional google.protobuf.Timestamp created = 1;\n  optional google.protobuf.StringValue name = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_valid_field_number() {
+        assert!(!valid_field_number(0));
+        assert!(!valid_field_number(19500));
+        assert!(valid_field_number(1));
+    }
+}
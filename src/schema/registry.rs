@@ -0,0 +1,51 @@
+use crate::schema::typedef::MessageDef;
+use std::collections::HashMap;
+
+/// Message definitions keyed by their full name. Used to resolve `google.protobuf.Any` type URLs
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    messages: HashMap<String, MessageDef>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Register a message definition under its full name, such as `com.apple.biome.AppLaunch`
+    pub fn register(&mut self, full_name: &str, message: MessageDef) {
+        self.messages
+            .insert(full_name.trim_start_matches('.').to_string(), message);
+    }
+
+    /// Get a message definition by its full name
+    pub fn get(&self, full_name: &str) -> Option<&MessageDef> {
+        self.messages.get(full_name.trim_start_matches('.'))
+    }
+
+    /// Get the message definition for an `Any` type URL. The type name is everything after the last `/`
+    pub fn resolve_type_url(&self, type_url: &str) -> Option<&MessageDef> {
+        let name = type_url.rsplit('/').next().unwrap_or(type_url);
+        self.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+    use crate::schema::typedef::MessageDef;
+
+    #[test]
+    fn test_schema() {
+        let mut schema = Schema::new();
+        schema.register(".test.Event", MessageDef::new("Event"));
+
+        assert!(schema.get("test.Event").is_some());
+        assert!(
+            schema
+                .resolve_type_url("type.googleapis.com/test.Event")
+                .is_some()
+        );
+        assert!(schema.resolve_type_url("test.Missing").is_none());
+    }
+}
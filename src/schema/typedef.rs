@@ -0,0 +1,488 @@
+use crate::{
+    light::{ProtoTag, ProtoValue, WireType},
+    tags::fixed::plausible_float,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// A best-guess definition of a Protobuf message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageDef {
+    pub name: String,
+    /**Fields keyed by field number */
+    pub fields: BTreeMap<usize, FieldDef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldDef {
+    pub number: usize,
+    pub name: String,
+    pub field_type: FieldType,
+    pub repeated: bool,
+    /**Observations that did not agree with the chosen `field_type` */
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FieldType {
+    Int64,
+    Uint64,
+    Double,
+    Float,
+    Fixed64,
+    Sfixed64,
+    Fixed32,
+    Sfixed32,
+    String,
+    Bytes,
+    Message(MessageDef),
+    Int32,
+    Uint32,
+    Sint32,
+    Sint64,
+    Bool,
+    /**A `google.protobuf` well-known type, rendered as its canonical JSON form */
+    WellKnown(WellKnownType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum WellKnownType {
+    Any,
+    Duration,
+    Empty,
+    FieldMask,
+    Struct,
+    Value,
+    ListValue,
+    Timestamp,
+    DoubleValue,
+    FloatValue,
+    Int64Value,
+    UInt64Value,
+    Int32Value,
+    UInt32Value,
+    BoolValue,
+    StringValue,
+    BytesValue,
+}
+
+impl MessageDef {
+    pub fn new(name: &str) -> MessageDef {
+        MessageDef {
+            name: name.to_string(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Add a field to the definition, replacing any existing field with the same number
+    pub fn add_field(&mut self, field: FieldDef) {
+        self.fields.insert(field.number, field);
+    }
+}
+
+impl FieldDef {
+    pub fn new(number: usize, name: &str, field_type: FieldType) -> FieldDef {
+        FieldDef {
+            number,
+            name: name.to_string(),
+            field_type,
+            repeated: false,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+impl FieldType {
+    /// The type name used in a `.proto` file
+    pub fn proto_name(&self) -> &str {
+        match self {
+            FieldType::Int64 => "int64",
+            FieldType::Uint64 => "uint64",
+            FieldType::Double => "double",
+            FieldType::Float => "float",
+            FieldType::Fixed64 => "fixed64",
+            FieldType::Sfixed64 => "sfixed64",
+            FieldType::Fixed32 => "fixed32",
+            FieldType::Sfixed32 => "sfixed32",
+            FieldType::String => "string",
+            FieldType::Bytes => "bytes",
+            FieldType::Message(message) => &message.name,
+            FieldType::Int32 => "int32",
+            FieldType::Uint32 => "uint32",
+            FieldType::Sint32 => "sint32",
+            FieldType::Sint64 => "sint64",
+            FieldType::Bool => "bool",
+            FieldType::WellKnown(well_known) => well_known.proto_name(),
+        }
+    }
+
+    /// Get the type from its `.proto` name. Message types cannot be looked up by name
+    pub fn from_proto_name(name: &str) -> Option<FieldType> {
+        let field_type = match name {
+            "int64" => FieldType::Int64,
+            "uint64" => FieldType::Uint64,
+            "double" => FieldType::Double,
+            "float" => FieldType::Float,
+            "fixed64" => FieldType::Fixed64,
+            "sfixed64" => FieldType::Sfixed64,
+            "fixed32" => FieldType::Fixed32,
+            "sfixed32" => FieldType::Sfixed32,
+            "string" => FieldType::String,
+            "bytes" => FieldType::Bytes,
+            "int32" => FieldType::Int32,
+            "uint32" => FieldType::Uint32,
+            "sint32" => FieldType::Sint32,
+            "sint64" => FieldType::Sint64,
+            "bool" => FieldType::Bool,
+            _ => FieldType::WellKnown(WellKnownType::from_proto_name(name)?),
+        };
+        Some(field_type)
+    }
+
+    /// The `WireType` used to encode the type
+    pub fn wire_type(&self) -> WireType {
+        match self {
+            FieldType::Int64
+            | FieldType::Uint64
+            | FieldType::Int32
+            | FieldType::Uint32
+            | FieldType::Sint32
+            | FieldType::Sint64
+            | FieldType::Bool => WireType::VarInt,
+            FieldType::Double | FieldType::Fixed64 | FieldType::Sfixed64 => WireType::Fixed64,
+            FieldType::Float | FieldType::Fixed32 | FieldType::Sfixed32 => WireType::Fixed32,
+            FieldType::String
+            | FieldType::Bytes
+            | FieldType::Message(_)
+            | FieldType::WellKnown(_) => WireType::Len,
+        }
+    }
+}
+
+impl WellKnownType {
+    /// The full type name used in a `.proto` file
+    pub fn proto_name(&self) -> &'static str {
+        match self {
+            WellKnownType::Any => "google.protobuf.Any",
+            WellKnownType::Duration => "google.protobuf.Duration",
+            WellKnownType::Empty => "google.protobuf.Empty",
+            WellKnownType::FieldMask => "google.protobuf.FieldMask",
+            WellKnownType::Struct => "google.protobuf.Struct",
+            WellKnownType::Value => "google.protobuf.Value",
+            WellKnownType::ListValue => "google.protobuf.ListValue",
+            WellKnownType::Timestamp => "google.protobuf.Timestamp",
+            WellKnownType::DoubleValue => "google.protobuf.DoubleValue",
+            WellKnownType::FloatValue => "google.protobuf.FloatValue",
+            WellKnownType::Int64Value => "google.protobuf.Int64Value",
+            WellKnownType::UInt64Value => "google.protobuf.UInt64Value",
+            WellKnownType::Int32Value => "google.protobuf.Int32Value",
+            WellKnownType::UInt32Value => "google.protobuf.UInt32Value",
+            WellKnownType::BoolValue => "google.protobuf.BoolValue",
+            WellKnownType::StringValue => "google.protobuf.StringValue",
+            WellKnownType::BytesValue => "google.protobuf.BytesValue",
+        }
+    }
+
+    /// Get the type from its full name. The leading `.` used in descriptors is optional
+    pub fn from_proto_name(name: &str) -> Option<WellKnownType> {
+        let well_known = match name.trim_start_matches('.') {
+            "google.protobuf.Any" => WellKnownType::Any,
+            "google.protobuf.Duration" => WellKnownType::Duration,
+            "google.protobuf.Empty" => WellKnownType::Empty,
+            "google.protobuf.FieldMask" => WellKnownType::FieldMask,
+            "google.protobuf.Struct" => WellKnownType::Struct,
+            "google.protobuf.Value" => WellKnownType::Value,
+            "google.protobuf.ListValue" => WellKnownType::ListValue,
+            "google.protobuf.Timestamp" => WellKnownType::Timestamp,
+            "google.protobuf.DoubleValue" => WellKnownType::DoubleValue,
+            "google.protobuf.FloatValue" => WellKnownType::FloatValue,
+            "google.protobuf.Int64Value" => WellKnownType::Int64Value,
+            "google.protobuf.UInt64Value" => WellKnownType::UInt64Value,
+            "google.protobuf.Int32Value" => WellKnownType::Int32Value,
+            "google.protobuf.UInt32Value" => WellKnownType::UInt32Value,
+            "google.protobuf.BoolValue" => WellKnownType::BoolValue,
+            "google.protobuf.StringValue" => WellKnownType::StringValue,
+            "google.protobuf.BytesValue" => WellKnownType::BytesValue,
+            _ => return None,
+        };
+        Some(well_known)
+    }
+
+    /// The file that must be imported to use the type in a `.proto` file
+    pub fn import_path(&self) -> &'static str {
+        match self {
+            WellKnownType::Any => "google/protobuf/any.proto",
+            WellKnownType::Duration => "google/protobuf/duration.proto",
+            WellKnownType::Empty => "google/protobuf/empty.proto",
+            WellKnownType::FieldMask => "google/protobuf/field_mask.proto",
+            WellKnownType::Struct | WellKnownType::Value | WellKnownType::ListValue => {
+                "google/protobuf/struct.proto"
+            }
+            WellKnownType::Timestamp => "google/protobuf/timestamp.proto",
+            WellKnownType::DoubleValue
+            | WellKnownType::FloatValue
+            | WellKnownType::Int64Value
+            | WellKnownType::UInt64Value
+            | WellKnownType::Int32Value
+            | WellKnownType::UInt32Value
+            | WellKnownType::BoolValue
+            | WellKnownType::StringValue
+            | WellKnownType::BytesValue => "google/protobuf/wrappers.proto",
+        }
+    }
+}
+
+/// Infer a best-guess `MessageDef` from Protobuf data returned by `extract_protobuf`
+pub fn infer_typedef(proto_map: &HashMap<usize, ProtoTag>) -> MessageDef {
+    infer_message("Message", proto_map)
+}
+
+/// Merge two definitions of the same message. Fields seen in only one definition are kept
+pub(crate) fn merge_messages(mut first: MessageDef, second: MessageDef) -> MessageDef {
+    for (number, field) in second.fields {
+        let merged = match first.fields.remove(&number) {
+            Some(existing) => merge_fields(existing, field),
+            None => field,
+        };
+        first.fields.insert(number, merged);
+    }
+    first
+}
+
+/// Name used for the nested message type of a field
+pub(crate) fn message_name(field: usize) -> String {
+    format!("Field{field}")
+}
+
+/// Name used for a field
+pub(crate) fn field_name(field: usize) -> String {
+    format!("field_{field}")
+}
+
+/// Infer the definition of a message from its fields
+fn infer_message(name: &str, proto_map: &HashMap<usize, ProtoTag>) -> MessageDef {
+    let mut message = MessageDef {
+        name: name.to_string(),
+        fields: BTreeMap::new(),
+    };
+
+    for (number, proto_tag) in proto_map {
+        let repeated = matches!(proto_tag.value, ProtoValue::Repeated(_));
+
+        let mut field: Option<FieldDef> = None;
+        for entry in proto_tag.value.values() {
+            let field_type = match observe_type(*number, entry) {
+                Some(result) => result,
+                None => continue,
+            };
+            let observed = FieldDef {
+                number: *number,
+                name: field_name(*number),
+                field_type,
+                repeated,
+                conflicts: Vec::new(),
+            };
+            field = Some(match field {
+                Some(existing) => merge_fields(existing, observed),
+                None => observed,
+            });
+        }
+
+        if let Some(result) = field {
+            message.fields.insert(*number, result);
+        }
+    }
+
+    message
+}
+
+/// Guess the type of a single decoded value. Deprecated groups are skipped
+pub(crate) fn observe_type(number: usize, value: &ProtoValue) -> Option<FieldType> {
+    let field_type = match value {
+        ProtoValue::VarInt(number) => {
+            if (*number as i64) < 0 {
+                FieldType::Int64
+            } else {
+                FieldType::Uint64
+            }
+        }
+        ProtoValue::Fixed64(bits) => {
+            if plausible_float(f64::from_bits(*bits)) {
+                FieldType::Double
+            } else if (*bits as i64) < 0 {
+                FieldType::Sfixed64
+            } else {
+                FieldType::Fixed64
+            }
+        }
+        ProtoValue::Fixed32(bits) => {
+            if plausible_float(f32::from_bits(*bits) as f64) {
+                FieldType::Float
+            } else if (*bits as i32) < 0 {
+                FieldType::Sfixed32
+            } else {
+                FieldType::Fixed32
+            }
+        }
+        ProtoValue::String(_) => FieldType::String,
+        ProtoValue::Bytes(_) => FieldType::Bytes,
+        ProtoValue::Message(sub) => FieldType::Message(infer_message(&message_name(number), sub)),
+        // Packed values are described by the type of their elements
+        ProtoValue::Packed(values) => return observe_type(number, values.first()?),
+        ProtoValue::Group(_) | ProtoValue::Repeated(_) => return None,
+    };
+    Some(field_type)
+}
+
+/// Merge two observations of the same field
+pub(crate) fn merge_fields(mut first: FieldDef, second: FieldDef) -> FieldDef {
+    first.repeated |= second.repeated;
+    first.conflicts.extend(second.conflicts);
+
+    let field_type = match (first.field_type, second.field_type) {
+        (FieldType::Message(message), FieldType::Message(other)) => {
+            FieldType::Message(merge_messages(message, other))
+        }
+        (existing, other) if existing == other => existing,
+        (FieldType::Int64 | FieldType::Uint64, FieldType::Int64 | FieldType::Uint64) => {
+            FieldType::Int64
+        }
+        (FieldType::Fixed64 | FieldType::Sfixed64, FieldType::Fixed64 | FieldType::Sfixed64) => {
+            FieldType::Sfixed64
+        }
+        (FieldType::Fixed32 | FieldType::Sfixed32, FieldType::Fixed32 | FieldType::Sfixed32) => {
+            FieldType::Sfixed32
+        }
+        (FieldType::String | FieldType::Bytes, FieldType::String | FieldType::Bytes) => {
+            FieldType::Bytes
+        }
+        (existing, other) => {
+            let note = format!(
+                "observed {} and {}",
+                type_label(&existing),
+                type_label(&other)
+            );
+            if !first.conflicts.contains(&note) {
+                first.conflicts.push(note);
+            }
+
+            if existing.wire_type() == other.wire_type() {
+                fallback_type(&existing.wire_type()).unwrap_or(existing)
+            } else {
+                existing
+            }
+        }
+    };
+
+    first.field_type = field_type;
+    first
+}
+
+/// Type used when two observations disagree but share a wire type. Bytes can hold both a string and a sub-message. Otherwise prefer the raw integer types
+fn fallback_type(wire_type: &WireType) -> Option<FieldType> {
+    let field_type = match wire_type {
+        WireType::VarInt => FieldType::Int64,
+        WireType::Fixed64 => FieldType::Fixed64,
+        WireType::Fixed32 => FieldType::Fixed32,
+        WireType::Len => FieldType::Bytes,
+        WireType::StartGroup | WireType::EndGroup | WireType::Unknown => return None,
+    };
+    Some(field_type)
+}
+
+/// Label used when reporting a conflict. Nested messages are all reported as `message`
+fn type_label(field_type: &FieldType) -> &str {
+    match field_type {
+        FieldType::Message(_) => "message",
+        _ => field_type.proto_name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldDef, FieldType, fallback_type, infer_typedef, merge_fields};
+    use crate::light::{WireType, extract_protobuf};
+    use crate::tags::fixed::plausible_float;
+
+    #[test]
+    fn test_infer_typedef() {
+        let test = [
+            16, 1, 24, 1, 33, 217, 236, 52, 46, 208, 118, 198, 65, 50, 28, 99, 111, 109, 46, 100,
+            117, 99, 107, 100, 117, 99, 107, 103, 111, 46, 109, 97, 99, 111, 115, 46, 98, 114, 111,
+            119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
+            0, 0, 0,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = infer_typedef(&proto_map);
+
+        assert_eq!(result.name, "Message");
+        assert_eq!(result.fields.len(), 9);
+        assert_eq!(result.fields[&4].field_type, FieldType::Double);
+        assert_eq!(result.fields[&6].field_type, FieldType::String);
+        assert_eq!(result.fields[&2].field_type, FieldType::Uint64);
+        assert!(result.fields[&0].repeated);
+        assert!(!result.fields[&6].repeated);
+    }
+
+    #[test]
+    fn test_infer_typedef_nested() {
+        let test = [
+            10, 3, 8, 150, 1, 10, 8, 8, 150, 1, 18, 3, 97, 98, 99, 18, 2, 10, 255,
+        ];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = infer_typedef(&proto_map);
+
+        let field = &result.fields[&1];
+        assert!(field.repeated);
+        match &field.field_type {
+            FieldType::Message(message) => {
+                assert_eq!(message.name, "Field1");
+                assert_eq!(message.fields[&1].field_type, FieldType::Uint64);
+                assert_eq!(message.fields[&2].field_type, FieldType::String);
+            }
+            _ => panic!("expected sub-message"),
+        }
+        // Not UTF8 and not a sub-message
+        assert_eq!(result.fields[&2].field_type, FieldType::Bytes);
+    }
+
+    #[test]
+    fn test_infer_typedef_conflict() {
+        // Field 1 is a sub-message and then a string
+        let test = [10, 3, 8, 150, 1, 10, 3, 97, 98, 99];
+        let proto_map = extract_protobuf(&test).unwrap();
+        let result = infer_typedef(&proto_map);
+
+        assert_eq!(result.fields[&1].field_type, FieldType::Bytes);
+        assert_eq!(
+            result.fields[&1].conflicts,
+            vec!["observed message and string"]
+        );
+    }
+
+    #[test]
+    fn test_merge_fields_same_wire_type() {
+        let first = FieldDef::new(1, "field1", FieldType::Int32);
+        let second = FieldDef::new(1, "field1", FieldType::Sint64);
+        let result = merge_fields(first, second);
+        assert_eq!(result.field_type, FieldType::Int64);
+        assert_eq!(result.conflicts, vec!["observed int32 and sint64"]);
+
+        // The merged type must use the same wire type so the data can still be decoded
+        for wire_type in [
+            WireType::VarInt,
+            WireType::Fixed64,
+            WireType::Fixed32,
+            WireType::Len,
+        ] {
+            assert_eq!(fallback_type(&wire_type).unwrap().wire_type(), wire_type);
+        }
+    }
+
+    #[test]
+    fn test_plausible_float() {
+        assert!(plausible_float(753770588.413478));
+        assert!(plausible_float(-1.0));
+        assert!(!plausible_float(4.1137624556819574e-11));
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    schema::{decode::decode_message, registry::Schema, typedef::WellKnownType},
+    tags::raw::{RawValue, parse_raw_fields},
+    utils::{
+        encoding::base64_encode_standard,
+        strings::borrow_utf8_str,
+        time::{fraction, unix_rfc3339},
+    },
+};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Convert a well-known type to its canonical JSON form. Returns `None` if the payload does not match the type, including text that is not valid UTF8
+pub(crate) fn well_known_value(
+    kind: WellKnownType,
+    payload: &[u8],
+    schema: &Schema,
+) -> Option<Value> {
+    let fields = raw_fields(payload)?;
+    let value = match kind {
+        WellKnownType::Timestamp => {
+            let seconds = varint(&fields, 1).unwrap_or_default() as i64;
+            let nanos = varint(&fields, 2).unwrap_or_default() as i32;
+            Value::String(unix_rfc3339(seconds, u32::try_from(nanos).ok()?)?)
+        }
+        WellKnownType::Duration => {
+            let seconds = varint(&fields, 1).unwrap_or_default() as i64;
+            let nanos = varint(&fields, 2).unwrap_or_default() as i32;
+            Value::String(duration(seconds, nanos))
+        }
+        WellKnownType::Empty => Value::Object(Map::new()),
+        WellKnownType::DoubleValue => match fields.get(&1).and_then(|values| values.last()) {
+            Some(RawValue::Fixed64(number)) => float_value(f64::from_bits(*number)),
+            Some(_) => return None,
+            None => Value::from(0.0),
+        },
+        WellKnownType::FloatValue => match fields.get(&1).and_then(|values| values.last()) {
+            Some(RawValue::Fixed32(number)) => float_value(f32::from_bits(*number) as f64),
+            Some(_) => return None,
+            None => Value::from(0.0),
+        },
+        // 64-bit integers are strings in canonical JSON
+        WellKnownType::Int64Value => {
+            Value::String((varint(&fields, 1).unwrap_or_default() as i64).to_string())
+        }
+        WellKnownType::UInt64Value => {
+            Value::String(varint(&fields, 1).unwrap_or_default().to_string())
+        }
+        WellKnownType::Int32Value => Value::from(varint(&fields, 1).unwrap_or_default() as i32),
+        WellKnownType::UInt32Value => Value::from(varint(&fields, 1).unwrap_or_default() as u32),
+        WellKnownType::BoolValue => Value::Bool(varint(&fields, 1).unwrap_or_default() != 0),
+        WellKnownType::StringValue => {
+            Value::String(borrow_utf8_str(bytes(&fields, 1).unwrap_or_default())?.to_string())
+        }
+        WellKnownType::BytesValue => Value::String(base64_encode_standard(
+            bytes(&fields, 1).unwrap_or_default(),
+        )),
+        WellKnownType::FieldMask => {
+            let mut paths = Vec::new();
+            for value in fields.get(&1).into_iter().flatten() {
+                match value {
+                    RawValue::Len(path) => paths.push(camel_case(borrow_utf8_str(path)?)),
+                    _ => return None,
+                }
+            }
+            Value::String(paths.join(","))
+        }
+        WellKnownType::Struct => struct_value(&fields)?,
+        WellKnownType::Value => value_value(&fields)?,
+        WellKnownType::ListValue => list_value(&fields)?,
+        WellKnownType::Any => any_value(&fields, schema)?,
+    };
+    Some(value)
+}
+
+/// Non-finite floats are strings in canonical JSON
+pub(crate) fn float_value(number: f64) -> Value {
+    if number.is_nan() {
+        Value::String(String::from("NaN"))
+    } else if number.is_infinite() && number.is_sign_positive() {
+        Value::String(String::from("Infinity"))
+    } else if number.is_infinite() {
+        Value::String(String::from("-Infinity"))
+    } else {
+        Value::from(number)
+    }
+}
+
+/// Group the values of a message by field number
+fn raw_fields(payload: &[u8]) -> Option<HashMap<usize, Vec<RawValue<'_>>>> {
+    let (_, fields) = parse_raw_fields(payload).ok()?;
+    let mut grouped: HashMap<usize, Vec<RawValue<'_>>> = HashMap::new();
+    for field in fields {
+        grouped
+            .entry(field.tag.field)
+            .or_default()
+            .push(field.value);
+    }
+    Some(grouped)
+}
+
+/// Get the last varint value of a field
+fn varint(fields: &HashMap<usize, Vec<RawValue<'_>>>, field: usize) -> Option<u64> {
+    match fields.get(&field)?.last()? {
+        RawValue::VarInt(number) => Some(*number),
+        _ => None,
+    }
+}
+
+/// Get the last length prefixed value of a field
+fn bytes<'a>(fields: &HashMap<usize, Vec<RawValue<'a>>>, field: usize) -> Option<&'a [u8]> {
+    match fields.get(&field)?.last()? {
+        RawValue::Len(payload) => Some(payload),
+        _ => None,
+    }
+}
+
+/// Format a duration such as `-1.500s`
+fn duration(seconds: i64, nanos: i32) -> String {
+    let sign = if seconds < 0 || nanos < 0 { "-" } else { "" };
+    format!(
+        "{sign}{}{}s",
+        seconds.unsigned_abs(),
+        fraction(nanos.unsigned_abs())
+    )
+}
+
+/// Convert a `snake_case` path to `lowerCamelCase`
+fn camel_case(path: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = false;
+    for character in path.chars() {
+        if character == '_' {
+            upper = true;
+        } else if upper {
+            camel.extend(character.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(character);
+        }
+    }
+    camel
+}
+
+/// `Struct` is a map of strings to `Value`
+fn struct_value(fields: &HashMap<usize, Vec<RawValue<'_>>>) -> Option<Value> {
+    let mut object = Map::new();
+    for entry in fields.get(&1).into_iter().flatten() {
+        let entry = match entry {
+            RawValue::Len(payload) => raw_fields(payload)?,
+            _ => return None,
+        };
+        let key = borrow_utf8_str(bytes(&entry, 1).unwrap_or_default())?.to_string();
+        let value = value_value(&raw_fields(bytes(&entry, 2).unwrap_or_default())?)?;
+        object.insert(key, value);
+    }
+    Some(Value::Object(object))
+}
+
+/// `Value` is a oneof of null, number, string, bool, `Struct` or `ListValue`
+fn value_value(fields: &HashMap<usize, Vec<RawValue<'_>>>) -> Option<Value> {
+    // Only one field of the oneof is expected, prefer the highest field number if several are set
+    let mut kinds: Vec<(&usize, &RawValue<'_>)> = fields
+        .iter()
+        .filter_map(|(field, values)| values.last().map(|value| (field, value)))
+        .collect();
+    kinds.sort_by_key(|(field, _)| **field);
+
+    let value = match kinds.last() {
+        None | Some((1, RawValue::VarInt(_))) => Value::Null,
+        Some((2, RawValue::Fixed64(number))) => float_value(f64::from_bits(*number)),
+        Some((3, RawValue::Len(payload))) => Value::String(borrow_utf8_str(payload)?.to_string()),
+        Some((4, RawValue::VarInt(number))) => Value::Bool(*number != 0),
+        Some((5, RawValue::Len(payload))) => struct_value(&raw_fields(payload)?)?,
+        Some((6, RawValue::Len(payload))) => list_value(&raw_fields(payload)?)?,
+        Some(_) => return None,
+    };
+    Some(value)
+}
+
+/// `ListValue` is a repeated `Value`
+fn list_value(fields: &HashMap<usize, Vec<RawValue<'_>>>) -> Option<Value> {
+    let mut values = Vec::new();
+    for value in fields.get(&1).into_iter().flatten() {
+        match value {
+            RawValue::Len(payload) => values.push(value_value(&raw_fields(payload)?)?),
+            _ => return None,
+        }
+    }
+    Some(Value::Array(values))
+}
+
+/// Any contains a type URL and the encoded message. The message is decoded if the type is registered in the schema or is a well-known type
+fn any_value(fields: &HashMap<usize, Vec<RawValue<'_>>>, schema: &Schema) -> Option<Value> {
+    let type_url = borrow_utf8_str(bytes(fields, 1).unwrap_or_default())?;
+    let payload = bytes(fields, 2).unwrap_or_default();
+    let type_name = type_url.rsplit('/').next().unwrap_or_default();
+
+    let mut object = Map::new();
+    object.insert(String::from("@type"), Value::String(type_url.to_string()));
+
+    if let Some(kind) = WellKnownType::from_proto_name(type_name) {
+        let value = well_known_value(kind, payload, schema)?;
+        object.insert(String::from("value"), value);
+    } else if let Some(message) = schema.resolve_type_url(type_url) {
+        object.extend(decode_message(payload, message, schema).ok()?);
+    } else {
+        object.insert(
+            String::from("value"),
+            Value::String(base64_encode_standard(payload)),
+        );
+    }
+    Some(Value::Object(object))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{camel_case, duration, float_value, well_known_value};
+    use crate::schema::{
+        registry::Schema,
+        typedef::{FieldDef, FieldType, MessageDef, WellKnownType},
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_well_known_timestamp() {
+        let test = [8, 128, 217, 137, 186, 6, 16, 192, 143, 174, 220, 3];
+        let result = well_known_value(WellKnownType::Timestamp, &test, &Schema::new()).unwrap();
+        assert_eq!(result, "2024-11-24T00:00:00.999Z");
+        assert_eq!(
+            well_known_value(WellKnownType::Timestamp, &[], &Schema::new()).unwrap(),
+            "1970-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(1, 500_000_000), "1.500s");
+        assert_eq!(duration(-3, -1), "-3.000000001s");
+        assert_eq!(duration(0, -10_000), "-0.000010s");
+        assert_eq!(duration(0, 0), "0s");
+    }
+
+    #[test]
+    fn test_well_known_wrappers() {
+        let schema = Schema::new();
+        assert_eq!(
+            well_known_value(WellKnownType::Int64Value, &[8, 255, 1], &schema).unwrap(),
+            "255"
+        );
+        assert_eq!(
+            well_known_value(WellKnownType::BoolValue, &[], &schema).unwrap(),
+            false
+        );
+        assert_eq!(
+            well_known_value(WellKnownType::StringValue, &[10, 2, 104, 105], &schema).unwrap(),
+            "hi"
+        );
+        let nan = f64::NAN.to_le_bytes();
+        let mut test = vec![9];
+        test.extend_from_slice(&nan);
+        assert_eq!(
+            well_known_value(WellKnownType::DoubleValue, &test, &schema).unwrap(),
+            "NaN"
+        );
+        assert_eq!(float_value(f64::NEG_INFINITY), "-Infinity");
+    }
+
+    #[test]
+    fn test_well_known_struct() {
+        // {"a": 1.0, "b": [true, null]}
+        let test = [
+            10, 14, 10, 1, 97, 18, 9, 17, 0, 0, 0, 0, 0, 0, 240, 63, 10, 15, 10, 1, 98, 18, 10, 50,
+            8, 10, 2, 32, 1, 10, 2, 8, 0,
+        ];
+        let result = well_known_value(WellKnownType::Struct, &test, &Schema::new()).unwrap();
+        assert_eq!(result, json!({"a": 1.0, "b": [true, null]}));
+    }
+
+    #[test]
+    fn test_well_known_field_mask() {
+        let test = [10, 9, 117, 115, 101, 114, 95, 110, 97, 109, 101, 10, 1, 120];
+        let result = well_known_value(WellKnownType::FieldMask, &test, &Schema::new()).unwrap();
+        assert_eq!(result, "userName,x");
+        assert_eq!(camel_case("a_b_c"), "aBC");
+    }
+
+    #[test]
+    fn test_well_known_invalid_utf8() {
+        // Text that is not valid UTF8 does not match the type
+        let schema = Schema::new();
+        let test = [10, 2, 255, 0];
+        assert!(well_known_value(WellKnownType::StringValue, &test, &schema).is_none());
+        assert!(well_known_value(WellKnownType::FieldMask, &test, &schema).is_none());
+        assert!(well_known_value(WellKnownType::Any, &test, &schema).is_none());
+
+        let test = [10, 8, 10, 2, 255, 0, 18, 2, 8, 0];
+        assert!(well_known_value(WellKnownType::Struct, &test, &schema).is_none());
+        assert!(well_known_value(WellKnownType::Value, &[26, 1, 255], &schema).is_none());
+    }
+
+    #[test]
+    fn test_well_known_any() {
+        let mut message = MessageDef::new("Event");
+        message.add_field(FieldDef::new(1, "count", FieldType::Uint32));
+        let mut schema = Schema::new();
+        schema.register("test.Event", message);
+
+        let type_url = b"type.googleapis.com/test.Event";
+        let mut test = vec![10, type_url.len() as u8];
+        test.extend_from_slice(type_url);
+        test.extend_from_slice(&[18, 2, 8, 5]);
+
+        let result = well_known_value(WellKnownType::Any, &test, &schema).unwrap();
+        assert_eq!(
+            result,
+            json!({"@type": "type.googleapis.com/test.Event", "count": 5})
+        );
+
+        let result = well_known_value(WellKnownType::Any, &test, &Schema::new()).unwrap();
+        assert_eq!(result["value"], "CAU=");
+
+        let type_url = b"type.googleapis.com/google.protobuf.Duration";
+        let mut test = vec![10, type_url.len() as u8];
+        test.extend_from_slice(type_url);
+        test.extend_from_slice(&[18, 2, 8, 5]);
+        let result = well_known_value(WellKnownType::Any, &test, &Schema::new()).unwrap();
+        assert_eq!(result["value"], "5s");
+    }
+}
@@ -0,0 +1,1059 @@
+use crate::{
+    encode::wire::{encode_length, encode_tag, encode_varint},
+    error::SunlightError,
+    light::{Tag, WireType},
+};
+use log::error;
+use serde::{
+    Serialize,
+    ser::{
+        self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+
+/// Options to control how Rust types are converted to wire values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerializeOptions {
+    /**Encode signed integers with zigzag encoding, like `sint32` and `sint64` */
+    pub zigzag: bool,
+}
+
+/// Largest field number allowed by Protobuf
+const MAX_FIELD: usize = (1 << 29) - 1;
+
+/// Serialize a struct or map to Protobuf bytes. Field numbers come from the field names, so each field should be renamed to its number or end with `_<number>`.
+/// Integers become varints, `f32` and `f64` become fixed values, and strings, bytes and structs are length prefixed. Repeated numbers are packed.
+/// `Vec<u8>` is a sequence of numbers to serde, use `serde_bytes` to write it as bytes
+///
+/// # Example
+/// ```rust
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Event {
+///     #[serde(rename = "1")]
+///     id: u64,
+///     name_2: String,
+///     #[serde(rename = "3")]
+///     tags: Vec<u32>,
+///     #[serde(rename = "6", skip_serializing_if = "Option::is_none")]
+///     note: Option<String>,
+/// }
+///
+/// let event = Event {
+///     id: 150,
+///     name_2: String::from("abc"),
+///     tags: vec![1, 2],
+///     note: None,
+/// };
+/// let proto_bytes = sunlight::ser::to_bytes(&event).unwrap();
+/// assert_eq!(proto_bytes, [8, 150, 1, 18, 3, 97, 98, 99, 26, 2, 1, 2]);
+/// ```
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SunlightError> {
+    to_bytes_options(value, SerializeOptions::default())
+}
+
+/// Serialize a struct or map to Protobuf bytes using the provided options
+pub fn to_bytes_options<T: Serialize + ?Sized>(
+    value: &T,
+    options: SerializeOptions,
+) -> Result<Vec<u8>, SunlightError> {
+    value.serialize(MessageSerializer { options })
+}
+
+/// A value ready to be written with a field number
+#[derive(Debug)]
+enum Encoded {
+    /**`None` and unit values are not written */
+    Skip,
+    VarInt(u64),
+    Fixed32(u32),
+    Fixed64(u64),
+    Len(Vec<u8>),
+    Repeated(Vec<Encoded>),
+}
+
+impl Encoded {
+    fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Encoded::VarInt(_) | Encoded::Fixed32(_) | Encoded::Fixed64(_)
+        )
+    }
+}
+
+/// Write a value with its tag. Repeated numbers of the same wire type are packed
+fn write_field(field: usize, value: Encoded, data: &mut Vec<u8>) -> Result<(), SunlightError> {
+    let tag = |wire_type| Tag {
+        tag_byte: 0,
+        wire_type,
+        field,
+    };
+    match value {
+        Encoded::Skip => {}
+        Encoded::VarInt(number) => {
+            encode_tag(&tag(WireType::VarInt), data);
+            encode_varint(number, data);
+        }
+        Encoded::Fixed32(bits) => {
+            encode_tag(&tag(WireType::Fixed32), data);
+            data.extend_from_slice(&bits.to_le_bytes());
+        }
+        Encoded::Fixed64(bits) => {
+            encode_tag(&tag(WireType::Fixed64), data);
+            data.extend_from_slice(&bits.to_le_bytes());
+        }
+        Encoded::Len(payload) => {
+            encode_tag(&tag(WireType::Len), data);
+            encode_length(&payload, data);
+        }
+        Encoded::Repeated(values) => {
+            let packable = values.first().is_some_and(Encoded::is_number)
+                && values.iter().all(|value| {
+                    std::mem::discriminant(value) == std::mem::discriminant(&values[0])
+                });
+            if !packable {
+                for value in values {
+                    if matches!(value, Encoded::Repeated(_)) {
+                        error!("[sunlight] field {field} is a sequence of sequences");
+                        return Err(SunlightError::Serialize);
+                    }
+                    write_field(field, value, data)?;
+                }
+                return Ok(());
+            }
+
+            let mut payload = Vec::new();
+            for value in values {
+                match value {
+                    Encoded::VarInt(number) => encode_varint(number, &mut payload),
+                    Encoded::Fixed32(bits) => payload.extend_from_slice(&bits.to_le_bytes()),
+                    Encoded::Fixed64(bits) => payload.extend_from_slice(&bits.to_le_bytes()),
+                    _ => {}
+                }
+            }
+            encode_tag(&tag(WireType::Len), data);
+            encode_length(&payload, data);
+        }
+    }
+    Ok(())
+}
+
+/// Get the field number from a field name like `6` or `event_6`
+fn parse_field_number(name: &str) -> Option<usize> {
+    let digits = name.rsplit('_').next().unwrap_or(name);
+    digits
+        .parse::<usize>()
+        .ok()
+        .filter(|field| (1..=MAX_FIELD).contains(field))
+}
+
+fn field_number(name: &str) -> Result<usize, SunlightError> {
+    parse_field_number(name).ok_or_else(|| {
+        error!("[sunlight] could not get a field number from {name}");
+        SunlightError::Serialize
+    })
+}
+
+fn unsupported(kind: &str) -> SunlightError {
+    error!("[sunlight] {kind} cannot be serialized here");
+    SunlightError::Serialize
+}
+
+/// Serializer for the top level message. Only structs and maps keyed by field number are allowed
+struct MessageSerializer {
+    options: SerializeOptions,
+}
+
+impl ser::Serializer for MessageSerializer {
+    type Ok = Vec<u8>;
+    type Error = SunlightError;
+    type SerializeSeq = Impossible<Vec<u8>, SunlightError>;
+    type SerializeTuple = Impossible<Vec<u8>, SunlightError>;
+    type SerializeTupleStruct = Impossible<Vec<u8>, SunlightError>;
+    type SerializeTupleVariant = Impossible<Vec<u8>, SunlightError>;
+    type SerializeMap = MessageEncoder;
+    type SerializeStruct = MessageEncoder;
+    type SerializeStructVariant = Impossible<Vec<u8>, SunlightError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a bool"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an integer"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a float"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a char"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a string"))
+    }
+
+    /// Bytes are assumed to already be an encoded message
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    /// A variant is a message with a single field numbered by the variant
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let mut data = Vec::new();
+        let value = value.serialize(ValueSerializer {
+            options: self.options,
+        })?;
+        write_field(variant_number(variant_index, variant), value, &mut data)?;
+        Ok(data)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(unsupported("a tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MessageEncoder::new(self.options))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(MessageEncoder::new(self.options))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(unsupported("a struct variant"))
+    }
+}
+
+/// Get the field number of an enum variant. Variants renamed to a number use it, otherwise the variant index starting at 1
+fn variant_number(variant_index: u32, variant: &str) -> usize {
+    parse_field_number(variant).unwrap_or(variant_index as usize + 1)
+}
+
+/// Writes the fields of a message
+struct MessageEncoder {
+    data: Vec<u8>,
+    field: Option<usize>,
+    options: SerializeOptions,
+}
+
+impl MessageEncoder {
+    fn new(options: SerializeOptions) -> Self {
+        MessageEncoder {
+            data: Vec::new(),
+            field: None,
+            options,
+        }
+    }
+
+    fn field<T: Serialize + ?Sized>(
+        &mut self,
+        field: usize,
+        value: &T,
+    ) -> Result<(), SunlightError> {
+        let value = value.serialize(ValueSerializer {
+            options: self.options,
+        })?;
+        write_field(field, value, &mut self.data)
+    }
+}
+
+impl SerializeStruct for MessageEncoder {
+    type Ok = Vec<u8>;
+    type Error = SunlightError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.field(field_number(key)?, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.data)
+    }
+}
+
+impl SerializeMap for MessageEncoder {
+    type Ok = Vec<u8>;
+    type Error = SunlightError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.field = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let field = self
+            .field
+            .take()
+            .ok_or_else(|| unsupported("a value without a key"))?;
+        self.field(field, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.data)
+    }
+}
+
+/// Serializer for map keys of a top level message. Keys must be field numbers
+struct KeySerializer;
+
+impl KeySerializer {
+    fn number(value: u64) -> Result<usize, SunlightError> {
+        match usize::try_from(value) {
+            Ok(field) if (1..=MAX_FIELD).contains(&field) => Ok(field),
+            _ => Err(unsupported("an invalid field number")),
+        }
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = usize;
+    type Error = SunlightError;
+    type SerializeSeq = Impossible<usize, SunlightError>;
+    type SerializeTuple = Impossible<usize, SunlightError>;
+    type SerializeTupleStruct = Impossible<usize, SunlightError>;
+    type SerializeTupleVariant = Impossible<usize, SunlightError>;
+    type SerializeMap = Impossible<usize, SunlightError>;
+    type SerializeStruct = Impossible<usize, SunlightError>;
+    type SerializeStructVariant = Impossible<usize, SunlightError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a bool key"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        let value = u64::try_from(v).map_err(|_err| unsupported("a negative field number"))?;
+        KeySerializer::number(value)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        KeySerializer::number(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        KeySerializer::number(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        KeySerializer::number(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        KeySerializer::number(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a float key"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a float key"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a char key"))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        field_number(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("a bytes key"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an empty key"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an empty key"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an empty key"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant_number(variant_index, variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("an enum key"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(unsupported("a sequence key"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(unsupported("a tuple key"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(unsupported("a tuple key"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(unsupported("a tuple key"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(unsupported("a map key"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(unsupported("a struct key"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(unsupported("a struct key"))
+    }
+}
+
+/// Serializer for a single field value
+struct ValueSerializer {
+    options: SerializeOptions,
+}
+
+impl ValueSerializer {
+    fn signed(&self, value: i64) -> Encoded {
+        if self.options.zigzag {
+            return Encoded::VarInt(((value << 1) ^ (value >> 63)) as u64);
+        }
+        Encoded::VarInt(value as u64)
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Encoded;
+    type Error = SunlightError;
+    type SerializeSeq = SeqEncoder;
+    type SerializeTuple = SeqEncoder;
+    type SerializeTupleStruct = SeqEncoder;
+    type SerializeTupleVariant = SeqEncoder;
+    type SerializeMap = MapEncoder;
+    type SerializeStruct = NestedEncoder;
+    type SerializeStructVariant = NestedEncoder;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(u64::from(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(self.signed(i64::from(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(self.signed(i64::from(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(self.signed(i64::from(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(self.signed(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(u64::from(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(u64::from(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(u64::from(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Fixed32(v.to_bits()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Fixed64(v.to_bits()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Len(v.to_string().into_bytes()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Len(v.as_bytes().to_vec()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Len(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Skip)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Skip)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Skip)
+    }
+
+    /// Protobuf enums are numbers. The variant index is used as the number
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::VarInt(u64::from(variant_index)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        MessageSerializer {
+            options: self.options,
+        }
+        .serialize_newtype_variant(name, variant_index, variant, value)
+        .map(Encoded::Len)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqEncoder {
+            values: Vec::with_capacity(len.unwrap_or_default()),
+            variant: None,
+            options: self.options,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SeqEncoder {
+            values: Vec::with_capacity(len),
+            variant: Some(variant_number(variant_index, variant)),
+            options: self.options,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapEncoder {
+            entries: Vec::new(),
+            key: None,
+            options: self.options,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(NestedEncoder {
+            message: MessageEncoder::new(self.options),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(NestedEncoder {
+            message: MessageEncoder::new(self.options),
+            variant: Some(variant_number(variant_index, variant)),
+        })
+    }
+}
+
+/// Wrap a value in a message with a single field numbered by the variant
+fn wrap_variant(variant: Option<usize>, value: Encoded) -> Result<Encoded, SunlightError> {
+    let Some(field) = variant else {
+        return Ok(value);
+    };
+    let mut data = Vec::new();
+    write_field(field, value, &mut data)?;
+    Ok(Encoded::Len(data))
+}
+
+/// Values of a repeated field
+struct SeqEncoder {
+    values: Vec<Encoded>,
+    variant: Option<usize>,
+    options: SerializeOptions,
+}
+
+impl SeqEncoder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SunlightError> {
+        self.values.push(value.serialize(ValueSerializer {
+            options: self.options,
+        })?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Encoded, SunlightError> {
+        wrap_variant(self.variant, Encoded::Repeated(self.values))
+    }
+}
+
+impl SerializeSeq for SeqEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for SeqEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for SeqEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for SeqEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Protobuf map field. Each entry is a message with the key in field 1 and the value in field 2
+struct MapEncoder {
+    entries: Vec<Encoded>,
+    key: Option<Encoded>,
+    options: SerializeOptions,
+}
+
+impl SerializeMap for MapEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(ValueSerializer {
+            options: self.options,
+        })?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| unsupported("a value without a key"))?;
+        let value = value.serialize(ValueSerializer {
+            options: self.options,
+        })?;
+        let mut entry = Vec::new();
+        write_field(1, key, &mut entry)?;
+        write_field(2, value, &mut entry)?;
+        self.entries.push(Encoded::Len(entry));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Encoded::Repeated(self.entries))
+    }
+}
+
+/// Sub-message field
+struct NestedEncoder {
+    message: MessageEncoder,
+    variant: Option<usize>,
+}
+
+impl SerializeStruct for NestedEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        SerializeStruct::serialize_field(&mut self.message, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        wrap_variant(self.variant, Encoded::Len(self.message.data))
+    }
+}
+
+impl SerializeStructVariant for NestedEncoder {
+    type Ok = Encoded;
+    type Error = SunlightError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        SerializeStruct::serialize_field(&mut self.message, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeStruct::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SerializeOptions, to_bytes, to_bytes_options};
+    use crate::{
+        de::{DeserializeOptions, from_bytes_options},
+        encode::text::text_to_protobuf,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Name {
+        #[serde(rename = "1")]
+        first: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Kind {
+        Unknown,
+        Person,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Person {
+        #[serde(rename = "1")]
+        id: i32,
+        #[serde(rename = "2")]
+        name: Name,
+        #[serde(rename = "3")]
+        scores: Vec<u32>,
+        #[serde(rename = "4")]
+        weight: f64,
+        #[serde(rename = "5")]
+        ratio: f32,
+        #[serde(rename = "6")]
+        kind: Kind,
+        #[serde(rename = "7")]
+        aliases: Vec<String>,
+        #[serde(rename = "8")]
+        labels: BTreeMap<String, u64>,
+        #[serde(rename = "9")]
+        missing: Option<u64>,
+        #[serde(rename = "10")]
+        active: bool,
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let person = Person {
+            id: -2,
+            name: Name {
+                first: String::from("Jane"),
+            },
+            scores: vec![1, 300],
+            weight: 100.0,
+            ratio: 1.5,
+            kind: Kind::Person,
+            aliases: vec![String::from("a"), String::from("b")],
+            labels: BTreeMap::from([(String::from("x"), 1)]),
+            missing: None,
+            active: true,
+        };
+        let data = to_bytes(&person).unwrap();
+        let expected = text_to_protobuf(
+            r#"
+            1: -2
+            2 { 1: "Jane" }
+            3: "\001\254\002"
+            4: 0x4059000000000000
+            5: 0x3fc00000
+            6: 1
+            7: "a"
+            7: "b"
+            8 { 1: "x" 2: 1 }
+            10: 1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(data, expected);
+
+        let options = DeserializeOptions::default();
+        let result: Person = from_bytes_options(&data, options).unwrap();
+        assert_eq!(result, person);
+    }
+
+    #[test]
+    fn test_to_bytes_zigzag() {
+        #[derive(Serialize)]
+        struct Signed {
+            #[serde(rename = "1")]
+            value: i64,
+            #[serde(rename = "2")]
+            packed: Vec<i32>,
+        }
+
+        let value = Signed {
+            value: -2,
+            packed: vec![-1, 2],
+        };
+        let options = SerializeOptions { zigzag: true };
+        assert_eq!(
+            to_bytes_options(&value, options).unwrap(),
+            [8, 3, 18, 2, 1, 4]
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_map() {
+        let message = HashMap::from([(1u32, "abc")]);
+        assert_eq!(to_bytes(&message).unwrap(), [10, 3, 97, 98, 99]);
+
+        #[derive(Serialize)]
+        struct Unnumbered {
+            name: String,
+        }
+        assert!(
+            to_bytes(&Unnumbered {
+                name: String::new()
+            })
+            .is_err()
+        );
+        assert!(to_bytes(&1u64).is_err());
+        assert!(to_bytes(&HashMap::from([(0u32, 1u32)])).is_err());
+    }
+}
@@ -1,63 +1,126 @@
-use crate::utils::nom_helper::{
-    Endian, nom_signed_eight_bytes, nom_signed_four_bytes, nom_unsigned_eight_bytes,
-    nom_unsigned_four_bytes,
-};
-use nom::{
-    bytes::complete::take,
-    number::{complete::le_f32, streaming::le_f64},
+use crate::{
+    render::json::FixedFormat,
+    utils::nom_helper::{Endian, nom_unsigned_eight_bytes, nom_unsigned_four_bytes},
 };
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Value, json};
 
 #[derive(Serialize)]
 pub(crate) struct Fixed64 {
     signed: i64,
     unsigned: u64,
-    double: f64,
+    double: Value,
 }
 
 #[derive(Serialize)]
 pub(crate) struct Fixed32 {
     signed: i32,
     unsigned: u32,
-    float: f32,
+    float: Value,
+}
+
+/// Parsed a fixed 8 byte value. This can be signed, unsiged or float64 (double). Its likely a float64
+pub(crate) fn parse_fixed64(data: &[u8]) -> nom::IResult<&[u8], u64> {
+    nom_unsigned_eight_bytes(data, Endian::Le)
+}
+
+/// Parsed a fixed 4 byte value. This can be signed, unsiged or float. Its likely a float
+pub(crate) fn parse_fixed32(data: &[u8]) -> nom::IResult<&[u8], u32> {
+    nom_unsigned_four_bytes(data, Endian::Le)
 }
 
-/// Parsed a fixed 8 byte value. This can be signed, unsiged or float64 (double). So we return all 3 options. Its likely a float64
-pub(crate) fn parse_fixed64(data: &[u8]) -> nom::IResult<&[u8], Value> {
-    let (_, signed) = nom_signed_eight_bytes(data, Endian::Le)?;
-    let (_, unsigned) = nom_unsigned_eight_bytes(data, Endian::Le)?;
-    let (input, float_bytes) = take(size_of::<f64>())(data)?;
+/// Get the JSON for a fixed 8 byte value. By default we return all 3 options
+pub(crate) fn fixed64_json(bits: u64, format: &FixedFormat) -> Value {
+    if *format == FixedFormat::BestGuess {
+        let (label, value) = best_fixed64(bits);
+        return json!({"type": label, "value": value});
+    }
 
-    let (_, double) = le_f64(float_bytes)?;
+    let double = f64::from_bits(bits);
     let fixed = Fixed64 {
-        signed,
-        unsigned,
-        double,
+        signed: bits as i64,
+        unsigned: bits,
+        double: float_value(double, bits),
     };
-
-    Ok((input, serde_json::to_value(fixed).unwrap_or(Value::Null)))
+    serde_json::to_value(fixed).unwrap_or(Value::Null)
 }
 
-/// Parsed a fixed 4 byte value. This can be signed, unsiged or float. So we return all 3 options. Its likely a float
-pub(crate) fn parse_fixed32(data: &[u8]) -> nom::IResult<&[u8], Value> {
-    let (_, signed) = nom_signed_four_bytes(data, Endian::Le)?;
-    let (_, unsigned) = nom_unsigned_four_bytes(data, Endian::Le)?;
-    let (input, float_bytes) = take(size_of::<f32>())(data)?;
+/// Get the JSON for a fixed 4 byte value. By default we return all 3 options
+pub(crate) fn fixed32_json(bits: u32, format: &FixedFormat) -> Value {
+    if *format == FixedFormat::BestGuess {
+        let (label, value) = best_fixed32(bits);
+        return json!({"type": label, "value": value});
+    }
 
-    let (_, float) = le_f32(float_bytes)?;
+    let float = f32::from_bits(bits);
     let fixed = Fixed32 {
-        signed,
-        unsigned,
-        float,
+        signed: bits as i32,
+        unsigned: bits,
+        float: float_value(float as f64, bits as u64),
     };
+    serde_json::to_value(fixed).unwrap_or(Value::Null)
+}
+
+/// Pick the most plausible interpretation of a fixed 8 byte value. Returns the Protobuf type name and the value
+fn best_fixed64(bits: u64) -> (&'static str, Value) {
+    let double = f64::from_bits(bits);
+    let signed = bits as i64;
+    if bits == 0 {
+        ("fixed64", Value::from(0))
+    } else if plausible_float(double) || special_float(double, bits == f64::NAN.to_bits()) {
+        ("double", float_value(double, bits))
+    } else if signed < 0 && signed >= i64::from(i32::MIN) {
+        // Negative numbers that sign-extend from 32 bits are likely signed
+        ("sfixed64", Value::from(signed))
+    } else {
+        ("fixed64", Value::from(bits))
+    }
+}
+
+/// Pick the most plausible interpretation of a fixed 4 byte value. Returns the Protobuf type name and the value
+fn best_fixed32(bits: u32) -> (&'static str, Value) {
+    let float = f32::from_bits(bits) as f64;
+    let signed = bits as i32;
+    if bits == 0 {
+        ("fixed32", Value::from(0))
+    } else if plausible_float(float) || special_float(float, bits == f32::NAN.to_bits()) {
+        ("float", float_value(float, bits as u64))
+    } else if signed < 0 && signed >= i32::from(i16::MIN) {
+        ("sfixed32", Value::from(signed))
+    } else {
+        ("fixed32", Value::from(bits))
+    }
+}
 
-    Ok((input, serde_json::to_value(fixed).unwrap_or(Value::Null)))
+/// Infinity and the default NaN are likely real floats. Other NaN bit patterns are usually integers
+fn special_float(value: f64, default_nan: bool) -> bool {
+    value.is_infinite() || default_nan
+}
+
+/// JSON has no NaN or Infinity. Those are returned as a hex string of the bits
+fn float_value(value: f64, bits: u64) -> Value {
+    if value.is_finite() {
+        return Value::from(value);
+    }
+    if bits > u64::from(u32::MAX) {
+        Value::String(format!("{bits:#018x}"))
+    } else {
+        Value::String(format!("{bits:#010x}"))
+    }
+}
+
+/// Check if a float has a reasonable magnitude. Integers reinterpreted as floats are usually tiny or huge
+pub(crate) fn plausible_float(value: f64) -> bool {
+    let min = 1e-7;
+    let max = 1e15;
+    value == 0.0 || (min..=max).contains(&value.abs())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_fixed32, parse_fixed64};
+    use super::{fixed32_json, fixed64_json, parse_fixed32, parse_fixed64};
+    use crate::render::json::FixedFormat;
+    use serde_json::json;
 
     #[test]
     fn test_parse_fixed64() {
@@ -68,8 +131,9 @@ mod tests {
         ];
         let (remaining, result) = parse_fixed64(&test).unwrap();
         assert_eq!(remaining.len(), 51);
+        assert_eq!(result, 4739606294354521305);
         assert_eq!(
-            result.to_string(),
+            fixed64_json(4739606294354521305, &FixedFormat::All).to_string(),
             "{\"double\":753770588.413478,\"signed\":4739606294354521305,\"unsigned\":4739606294354521305}"
         );
     }
@@ -79,9 +143,31 @@ mod tests {
         let test = [217, 236, 52, 46];
         let (remaining, result) = parse_fixed32(&test).unwrap();
         assert_eq!(remaining.len(), 0);
+        assert_eq!(result, 775220441);
         assert_eq!(
-            result.to_string(),
+            fixed32_json(775220441, &FixedFormat::All).to_string(),
             "{\"float\":4.1137624556819574e-11,\"signed\":775220441,\"unsigned\":775220441}"
         );
     }
+
+    #[test]
+    fn test_fixed_json_best_guess() {
+        let result = fixed64_json(4739606294354521305, &FixedFormat::BestGuess);
+        assert_eq!(result, json!({"type": "double", "value": 753770588.413478}));
+
+        let result = fixed64_json(u64::MAX, &FixedFormat::BestGuess);
+        assert_eq!(result, json!({"type": "sfixed64", "value": -1}));
+
+        let result = fixed32_json(42, &FixedFormat::BestGuess);
+        assert_eq!(result, json!({"type": "fixed32", "value": 42}));
+
+        let result = fixed32_json(f32::NAN.to_bits(), &FixedFormat::BestGuess);
+        assert_eq!(result, json!({"type": "float", "value": "0x7fc00000"}));
+    }
+
+    #[test]
+    fn test_fixed_json_nan() {
+        let result = fixed64_json(f64::INFINITY.to_bits(), &FixedFormat::All);
+        assert_eq!(result["double"], "0x7ff0000000000000");
+    }
 }
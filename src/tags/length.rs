@@ -1,44 +1,36 @@
 use crate::{
-    tags::parser::parse_tag,
-    utils::{
-        encoding::base64_encode_standard,
-        nom_helper::{Endian, nom_unsigned_one_byte},
-        strings::extract_utf8_string,
-    },
+    light::{ExtractOptions, ProtoValue},
+    tags::{parser::parse_tag, var::parse_varint},
+    utils::strings::borrow_utf8_str,
 };
 use nom::bytes::complete::take;
-use serde_json::Value;
 
-/// Parse length based tags. The value can be either a string or nested object (sub-message)
-pub(crate) fn parse_length_tag(data: &[u8]) -> nom::IResult<&[u8], Value> {
-    let (input, value_length) = nom_unsigned_one_byte(data, Endian::Le)?;
-    let (input, value) = take(value_length)(input)?;
+/// Get the length prefixed payload bytes
+pub(crate) fn parse_length_payload(data: &[u8]) -> nom::IResult<&[u8], &[u8]> {
+    let (input, value_length) = parse_varint(data)?;
+    take(value_length)(input)
+}
 
+/// Parse length based tags. The value can be either a string or nested object (sub-message). Offset is the position of the payload in the original data
+pub(crate) fn length_value(value: &[u8], offset: usize, options: &ExtractOptions) -> ProtoValue {
     // Try string parsing first
-    let message = extract_utf8_string(value);
+    if let Some(message) = borrow_utf8_str(value) {
+        return ProtoValue::String(message.to_string());
+    }
 
     // If we fail, fallback to sub-message parsing
-    if message.starts_with("Failed to get UTF8 string") {
-        let result = parse_tag(value);
-        let sub = match result {
-            Ok((_, result)) => result,
-            Err(_err) => {
-                // If not string or submessage might be raw bytes?
-                return Ok((
-                    input,
-                    serde_json::to_value(base64_encode_standard(value)).unwrap_or(Value::Null),
-                ));
-            }
-        };
-        return Ok((input, serde_json::to_value(sub).unwrap_or(Value::Null)));
+    let result = parse_tag(value, offset, options);
+    match result {
+        Ok((_, result)) => ProtoValue::Message(result),
+        // If not string or submessage might be raw bytes?
+        Err(_err) => ProtoValue::Bytes(value.to_vec()),
     }
-
-    Ok((input, Value::String(message)))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_length_tag;
+    use super::{length_value, parse_length_payload};
+    use crate::light::ExtractOptions;
 
     #[test]
     fn test_parse_length_tag() {
@@ -52,8 +44,27 @@ mod tests {
             77, 105, 103, 114, 97, 116, 111, 114, 65, 114, 99, 97, 100, 101, 84, 97, 115, 107,
         ];
 
-        let (remaining, result) = parse_length_tag(&test).unwrap();
-        assert_eq!(result, "com.apple.appstored.MigratorMiscellaneousTask");
+        let (remaining, payload) = parse_length_payload(&test).unwrap();
+        let result = length_value(payload, 1, &ExtractOptions::default());
+        assert_eq!(
+            result.as_str().unwrap(),
+            "com.apple.appstored.MigratorMiscellaneousTask"
+        );
         assert_eq!(remaining.len(), 82);
     }
+
+    #[test]
+    fn test_parse_length_tag_long_prefix() {
+        // Payloads of 128 bytes or more use a multi-byte length prefix
+        let mut test = vec![128, 1];
+        test.extend(vec![b'a'; 128]);
+        test.push(8);
+
+        let (remaining, payload) = parse_length_payload(&test).unwrap();
+        assert_eq!(payload.len(), 128);
+        assert_eq!(remaining, [8]);
+
+        // Truncated length prefix
+        assert!(parse_length_payload(&[128]).is_err());
+    }
 }
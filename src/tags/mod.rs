@@ -1,5 +1,6 @@
-mod fixed;
-mod length;
+pub(crate) mod fixed;
+pub(crate) mod length;
 pub(crate) mod parser;
-mod tag;
-mod var;
+pub(crate) mod raw;
+pub(crate) mod tag;
+pub(crate) mod var;
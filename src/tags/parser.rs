@@ -1,75 +1,200 @@
-use super::{length::parse_length_tag, tag::get_tag_type};
 use crate::{
-    light::{ProtoTag, WireType},
-    tags::{
-        fixed::{parse_fixed32, parse_fixed64},
-        var::parse_var,
-    },
-    utils::encoding::base64_encode_standard,
+    error::SunlightError,
+    light::{ExtractOptions, ProtoTag, ProtoValue, WireRecord, WireType},
+    tags::raw::RawValue,
+    utils::strings::borrow_utf8_str,
+    view::{FieldView, MessageView},
+    visit::{Visit, Visitor, walk},
 };
 use log::warn;
-use serde_json::Value;
+use nom::error::{Error, ErrorKind};
 use std::collections::HashMap;
 
-/// Extract the Protobuf values from the provided data
-pub(crate) fn parse_tag(data: &[u8]) -> nom::IResult<&[u8], HashMap<usize, ProtoTag>> {
-    let mut proto_data = data;
-    let mut proto_map: HashMap<usize, ProtoTag> = HashMap::new();
-
-    while !proto_data.is_empty() {
-        let (input, tag) = get_tag_type(proto_data)?;
-
-        let (input, value) = match tag.wire_type {
-            WireType::VarInt => parse_var(input)?,
-            WireType::Fixed64 => parse_fixed64(input)?,
-            WireType::Len => parse_length_tag(input)?,
-            WireType::StartGroup => {
-                warn!(
-                    "[sunlight] got start group wiretype. This is deprecated, ending parsing now. Returning base64 as final result"
-                );
-                ([].as_slice(), Value::String(base64_encode_standard(input)))
-            }
-            WireType::EndGroup => {
-                warn!(
-                    "[sunlight] got end group wiretype. This is deprecated, ending parsing now. Returning base64 as final result"
-                );
-                ([].as_slice(), Value::String(base64_encode_standard(input)))
-            }
-            WireType::Fixed32 => parse_fixed32(input)?,
-            WireType::Unknown => {
-                warn!(
-                    "[sunlight] got unknown wire type. Protobuf data may be corrupted or this is not protobuf data, ending parsing now. Returning base64 as final result"
-                );
-                ([].as_slice(), Value::String(base64_encode_standard(input)))
-            }
+/// Extract the Protobuf values from the provided data. Offset is the position of the data in the original bytes
+pub(crate) fn parse_tag<'a>(
+    data: &'a [u8],
+    offset: usize,
+    options: &ExtractOptions,
+) -> nom::IResult<&'a [u8], HashMap<usize, ProtoTag>> {
+    let mut builder = MapBuilder {
+        options,
+        frames: vec![Frame::default()],
+        path: Vec::new(),
+    };
+    walk(MessageView::at(data, offset), &mut builder);
+
+    let Some(frame) = builder.frames.pop() else {
+        return Err(nom::Err::Error(Error::new(data, ErrorKind::Fail)));
+    };
+    if let Some(failed) = frame.failed {
+        return Err(nom::Err::Error(Error::new(
+            &data[failed - offset..],
+            ErrorKind::Verify,
+        )));
+    }
+    Ok(([].as_slice(), frame.proto_map))
+}
+
+/// Fields of the message currently being built
+#[derive(Default)]
+struct Frame {
+    proto_map: HashMap<usize, ProtoTag>,
+    /**Offset of the field that could not be parsed */
+    failed: Option<usize>,
+}
+
+/// Builds the `HashMap` returned by `extract_protobuf`. Length prefixed values are strings if they are valid UTF8, otherwise sub-messages. Sub-messages that cannot be parsed are bytes
+struct MapBuilder<'o> {
+    options: &'o ExtractOptions,
+    frames: Vec<Frame>,
+    /**Field numbers of the sub-messages being built */
+    path: Vec<usize>,
+}
+
+impl MapBuilder<'_> {
+    /// Add a value to the current message
+    fn insert(&mut self, field: &FieldView<'_>, value: ProtoValue) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        // Keep the exact bytes of the field so non-canonical encodings can be written back.
+        // Sub-messages only keep their tag and length prefix since their fields keep their own bytes. Copying the whole payload would copy nested bytes again at every level
+        let raw = field.raw();
+        let header = &raw[..raw.len() - field.as_bytes().map_or(0, <[u8]>::len)];
+        let record = WireRecord {
+            offset: field.offset(),
+            // Sub-messages that stopped at a group and strings with trailing NULL characters removed cannot be encoded again from the value, so their payload is always kept
+            payload: field
+                .as_bytes()
+                .filter(|payload| match &value {
+                    ProtoValue::Message(sub) => has_group(sub),
+                    ProtoValue::String(text) => {
+                        self.options.lossless || text.len() != payload.len()
+                    }
+                    _ => self.options.lossless,
+                })
+                .map(<[u8]>::to_vec),
+            raw: self.options.lossless.then(|| match &value {
+                ProtoValue::Message(_) => header.to_vec(),
+                _ => raw.to_vec(),
+            }),
+            edited: false,
         };
 
-        // Existing field found. Value is should be an array
-        if let Some(existing_field) = proto_map.get_mut(&tag.field) {
-            if existing_field.value.is_array() {
-                existing_field.value.as_array_mut().unwrap().push(value);
+        // Existing field found. Value is should be repeated
+        if let Some(existing_field) = frame.proto_map.get_mut(&field.field()) {
+            if let ProtoValue::Repeated(values) = &mut existing_field.value {
+                values.push(value);
             } else {
-                // Convert data to array of values
-                existing_field.value = Value::Array(vec![existing_field.value.clone(), value]);
+                // Convert data to repeated values
+                let existing =
+                    std::mem::replace(&mut existing_field.value, ProtoValue::Repeated(Vec::new()));
+                existing_field.value = ProtoValue::Repeated(vec![existing, value]);
             }
+            existing_field.records.push(record);
         } else {
-            let proto_tag = ProtoTag { tag, value };
-            proto_map.insert(proto_tag.tag.field, proto_tag);
+            let value = if self.options.arrays.is_repeated(&self.path, field.field()) {
+                ProtoValue::Repeated(vec![value])
+            } else {
+                value
+            };
+            let proto_tag = ProtoTag {
+                tag: field.tag().clone(),
+                value,
+                records: vec![record],
+            };
+            frame.proto_map.insert(proto_tag.tag.field, proto_tag);
         }
+    }
+}
+
+impl<'a> Visitor<'a> for MapBuilder<'_> {
+    fn enter_message(&mut self, field: &FieldView<'a>) {
+        self.frames.push(Frame::default());
+        self.path.push(field.field());
+    }
 
-        proto_data = input;
+    fn field(&mut self, field: &FieldView<'a>) -> Visit {
+        let value = match field.value {
+            RawValue::VarInt(number) => ProtoValue::VarInt(number),
+            RawValue::Fixed64(bits) => ProtoValue::Fixed64(bits),
+            RawValue::Fixed32(bits) => ProtoValue::Fixed32(bits),
+            RawValue::Len(payload) => match borrow_utf8_str(payload) {
+                // Try string parsing first
+                Some(message) => ProtoValue::String(message.to_string()),
+                // If we fail, fallback to sub-message parsing
+                None => return Visit::Enter,
+            },
+            RawValue::Remaining(remaining) => {
+                match field.wire_type() {
+                    WireType::StartGroup => warn!(
+                        "[sunlight] got start group wiretype. This is deprecated, ending parsing now. Returning remaining bytes as final result"
+                    ),
+                    WireType::EndGroup => warn!(
+                        "[sunlight] got end group wiretype. This is deprecated, ending parsing now. Returning remaining bytes as final result"
+                    ),
+                    _ => warn!(
+                        "[sunlight] got unknown wire type. Protobuf data may be corrupted or this is not protobuf data, ending parsing now. Returning remaining bytes as final result"
+                    ),
+                }
+                ProtoValue::Group(remaining.to_vec())
+            }
+        };
+        self.insert(field, value);
+        Visit::Continue
+    }
+
+    fn exit_message(&mut self, field: &FieldView<'a>) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        self.path.pop();
+        let value = match (frame.failed, field.as_bytes()) {
+            (None, _) => ProtoValue::Message(frame.proto_map),
+            // If not string or submessage might be raw bytes?
+            (Some(_), payload) => ProtoValue::Bytes(payload.unwrap_or_default().to_vec()),
+        };
+        self.insert(field, value);
+    }
+
+    fn error(&mut self, offset: usize, _error: &SunlightError) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.failed = Some(offset);
+        }
     }
+}
 
-    Ok((proto_data, proto_map))
+/// Check if parsing stopped at a group or unknown wire type. `protoc` would not treat these as sub-messages
+pub(crate) fn has_group(proto_map: &HashMap<usize, ProtoTag>) -> bool {
+    proto_map.values().any(|proto_tag| {
+        proto_tag
+            .value
+            .values()
+            .iter()
+            .any(|value| matches!(value, ProtoValue::Group(_)))
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::parse_tag;
-    use crate::light::WireType;
+    use crate::{
+        encode::text::text_to_protobuf,
+        light::{ArrayMode, ExtractOptions, ProtoValue, RepeatedHints, WireType},
+        render::json::{JsonOptions, value_to_json},
+    };
     use serde_json::Value;
     use std::{fs::read, path::PathBuf};
 
+    fn json(value: &ProtoValue) -> Value {
+        value_to_json(value, &JsonOptions::default())
+    }
+
+    /// Encode Protobuf text format. Used to check the text form of byte exact test input
+    fn fixture(text: &str) -> Vec<u8> {
+        text_to_protobuf(text).unwrap()
+    }
+
     #[test]
     fn test_parse_tag() {
         let test = [
@@ -82,11 +207,11 @@ mod tests {
             77, 105, 103, 114, 97, 116, 111, 114, 65, 114, 99, 97, 100, 101, 84, 97, 115, 107,
         ];
 
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(
-            result.get(&1).unwrap().value,
+            json(&result.get(&1).unwrap().value),
             Value::Array(vec![
                 Value::String(String::from(
                     "com.apple.appstored.MigratorMiscellaneousTask"
@@ -105,31 +230,102 @@ mod tests {
             48, 50, 57, 57, 57, 57, 51, 101, 101, 53, 100, 97, 56, 48, 56, 48, 98, 51, 57, 53, 51,
             57, 57, 101, 56, 50, 54,
         ];
+        let text = r#"
+            1: "production"
+            2: "c44e10299993ee5da8080b395399e826"
+            "#;
+        assert_eq!(fixture(text), test);
 
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 2);
-        assert_eq!(result.get(&1).unwrap().value, "production");
+        assert_eq!(json(&result.get(&1).unwrap().value), "production");
         assert_eq!(
-            result.get(&2).unwrap().value,
+            json(&result.get(&2).unwrap().value),
             "c44e10299993ee5da8080b395399e826"
         );
     }
 
+    #[test]
+    fn test_parse_tag_payloads() {
+        // Field 2 is a sub-message and field 3 is a sub-message that stops at an unknown wire type
+        let test = [8, 1, 18, 3, 8, 150, 1, 26, 2, 255, 0];
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
+        assert_eq!(result[&2].records[0].payload, None);
+        assert_eq!(result[&3].records[0].payload, Some(vec![255, 0]));
+
+        let options = ExtractOptions {
+            lossless: true,
+            ..Default::default()
+        };
+        let (_, result) = parse_tag(&test, 0, &options).unwrap();
+        // Sub-messages are rebuilt from their fields
+        assert_eq!(result[&2].records[0].payload, None);
+        assert_eq!(result[&2].records[0].raw, Some(vec![18, 3]));
+        let sub = result[&2].value.as_message().unwrap();
+        assert_eq!(sub[&1].records[0].raw, Some(vec![8, 150, 1]));
+
+        // Trailing NULL characters are removed from the string but kept in the payload
+        let test = [10, 4, 97, 98, 0, 0, 18, 1, 97];
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
+        assert_eq!(result[&1].value, ProtoValue::String(String::from("ab")));
+        assert_eq!(result[&1].records[0].payload, Some(vec![97, 98, 0, 0]));
+        assert_eq!(result[&2].records[0].payload, None);
+    }
+
+    #[test]
+    fn test_parse_tag_arrays() {
+        let test = [8, 150, 1, 18, 6, 8, 172, 2, 24, 200, 1, 18, 3, 8, 173, 2];
+        assert_eq!(fixture("1: 150 2 { 1: 300 3: 200 } 2 { 1: 301 }"), test);
+
+        let options = ExtractOptions {
+            arrays: ArrayMode::Always,
+            ..Default::default()
+        };
+        let (_, result) = parse_tag(&test, 0, &options).unwrap();
+        assert_eq!(
+            result[&1].value,
+            ProtoValue::Repeated(vec![ProtoValue::VarInt(150)])
+        );
+        let messages = result[&2].value.values();
+        assert_eq!(messages.len(), 2);
+        let second = messages[1].as_message().unwrap();
+        assert_eq!(
+            second[&1].value,
+            ProtoValue::Repeated(vec![ProtoValue::VarInt(301)])
+        );
+
+        let mut hints = RepeatedHints::new();
+        hints.add(&[2, 3]);
+        let options = ExtractOptions {
+            arrays: ArrayMode::Hints(hints),
+            ..Default::default()
+        };
+        let (_, result) = parse_tag(&test, 0, &options).unwrap();
+        assert_eq!(result[&1].value, ProtoValue::VarInt(150));
+        let first = result[&2].value.values()[0].as_message().unwrap();
+        assert_eq!(first[&1].value, ProtoValue::VarInt(300));
+        assert_eq!(
+            first[&3].value,
+            ProtoValue::Repeated(vec![ProtoValue::VarInt(200)])
+        );
+        assert_eq!(result[&2].records.len(), 2);
+    }
+
     #[test]
     fn test_parse_tag_biome() {
         let test = [
             10, 15, 55, 53, 48, 48, 53, 54, 55, 57, 57, 54, 48, 56, 53, 57, 56, 16, 240, 249, 7,
             24, 61, 32, 1, 42, 10, 66, 105, 111, 109, 101, 65, 103, 101, 110, 116, 0, 0, 0,
         ];
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 6);
-        assert_eq!(result.get(&4).unwrap().value, 1);
+        assert_eq!(json(&result.get(&4).unwrap().value), 1);
         assert_eq!(
-            result.get(&0).unwrap().value.as_array().unwrap(),
+            json(&result.get(&0).unwrap().value).as_array().unwrap(),
             &vec![0, 0]
         );
-        assert_eq!(result.get(&5).unwrap().value, "BiomeAgent");
-        assert_eq!(result.get(&1).unwrap().value, "750056799608598");
+        assert_eq!(json(&result.get(&5).unwrap().value), "BiomeAgent");
+        assert_eq!(json(&result.get(&1).unwrap().value), "750056799608598");
     }
 
     #[test]
@@ -140,21 +336,21 @@ mod tests {
             119, 115, 101, 114, 74, 7, 49, 46, 49, 49, 52, 46, 48, 82, 3, 51, 48, 56, 88, 1, 96, 1,
             0, 0, 0,
         ];
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 9);
         assert_eq!(
-            result.get(&4).unwrap().value.to_string(),
+            json(&result.get(&4).unwrap().value).to_string(),
             "{\"double\":753770588.413478,\"signed\":4739606294354521305,\"unsigned\":4739606294354521305}"
         );
         assert_eq!(
-            result.get(&0).unwrap().value.as_array().unwrap(),
+            json(&result.get(&0).unwrap().value).as_array().unwrap(),
             &vec![0, 0]
         );
         assert_eq!(
-            result.get(&6).unwrap().value,
+            json(&result.get(&6).unwrap().value),
             "com.duckduckgo.macos.browser"
         );
-        assert_eq!(result.get(&9).unwrap().value, "1.114.0");
+        assert_eq!(json(&result.get(&9).unwrap().value), "1.114.0");
     }
 
     #[test]
@@ -165,19 +361,22 @@ mod tests {
             101, 50, 74, 4, 52, 46, 55, 54, 82, 13, 52, 46, 55, 54, 46, 50, 52, 49, 48, 49, 51, 56,
             55, 88, 1, 96, 1, 0, 0, 0,
         ];
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 9);
         assert_eq!(
-            result.get(&4).unwrap().value.to_string(),
+            json(&result.get(&4).unwrap().value).to_string(),
             "{\"double\":753768250.446566,\"signed\":4739606274742233363,\"unsigned\":4739606274742233363}"
         );
         assert_eq!(
-            result.get(&0).unwrap().value.as_array().unwrap(),
+            json(&result.get(&0).unwrap().value).as_array().unwrap(),
             &vec![0, 0]
         );
-        assert_eq!(result.get(&6).unwrap().value, "com.microsoft.autoupdate2");
-        assert_eq!(result.get(&10).unwrap().value, "4.76.24101387");
-        assert_eq!(result.get(&9).unwrap().value, "4.76");
+        assert_eq!(
+            json(&result.get(&6).unwrap().value),
+            "com.microsoft.autoupdate2"
+        );
+        assert_eq!(json(&result.get(&10).unwrap().value), "4.76.24101387");
+        assert_eq!(json(&result.get(&9).unwrap().value), "4.76");
     }
 
     #[test]
@@ -188,14 +387,20 @@ mod tests {
             101, 110, 115, 105, 111, 110, 46, 115, 99, 111, 114, 101, 99, 97, 114, 100, 46, 100,
             97, 105, 108, 121, 26, 11, 78, 111, 116, 32, 83, 116, 97, 114, 116, 101, 100,
         ];
-        let (_, result) = parse_tag(&test).unwrap();
+        let text = r#"
+            1: 1
+            2: "com.apple.siri.metrics.MetricsExtension.scorecard.daily"
+            3: "Not Started"
+            "#;
+        assert_eq!(fixture(text), test);
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 3);
         assert_eq!(
-            result.get(&2).unwrap().value,
+            json(&result.get(&2).unwrap().value),
             "com.apple.siri.metrics.MetricsExtension.scorecard.daily"
         );
-        assert_eq!(result.get(&3).unwrap().value, "Not Started");
-        assert_eq!(result.get(&1).unwrap().value, 1);
+        assert_eq!(json(&result.get(&3).unwrap().value), "Not Started");
+        assert_eq!(json(&result.get(&1).unwrap().value), 1);
     }
 
     #[test]
@@ -210,18 +415,18 @@ mod tests {
             0, 0, 0, 240, 191, 97, 0, 0, 0, 192, 204, 255, 42, 64, 105, 0, 0, 0, 0, 0, 0, 240, 191,
             113, 0, 0, 0, 0, 0, 0, 240, 191, 0, 0,
         ];
-        let (_, result) = parse_tag(&test).unwrap();
+        let (_, result) = parse_tag(&test, 0, &ExtractOptions::default()).unwrap();
         assert_eq!(result.len(), 15);
         assert_eq!(
-            result.get(&4).unwrap().value,
+            json(&result.get(&4).unwrap().value),
             "com.apple.siri.metrics.MetricsExtension.scorecard.daily"
         );
         assert_eq!(
-            result.get(&3).unwrap().value.to_string(),
+            json(&result.get(&3).unwrap().value).to_string(),
             "{\"double\":1732406400.0,\"signed\":4745053047086907392,\"unsigned\":4745053047086907392}"
         );
         assert_eq!(
-            result.get(&12).unwrap().value.to_string(),
+            json(&result.get(&12).unwrap().value).to_string(),
             "{\"double\":13.499608993530273,\"signed\":4623789222308872192,\"unsigned\":4623789222308872192}"
         );
     }
@@ -232,15 +437,15 @@ mod tests {
         test_path.push("tests/test_data/blackboxprotobuf/test_message.out");
         let data = read(test_path.to_str().unwrap()).unwrap();
 
-        let (_, result) = parse_tag(&data).unwrap();
-        assert_eq!(serde_json::to_string(&result).unwrap().len(), 1856);
-        assert_eq!(result.get(&128).unwrap().value, 1);
+        let (_, result) = parse_tag(&data, 0, &ExtractOptions::default()).unwrap();
+        assert_eq!(serde_json::to_string(&result).unwrap().len(), 1880);
+        assert_eq!(json(&result.get(&128).unwrap().value), 1);
         assert_eq!(
-            result.get(&1024).unwrap().value.to_string(),
-            "{\"float\":null,\"signed\":-20,\"unsigned\":4294967276}"
+            json(&result.get(&1024).unwrap().value).to_string(),
+            "{\"float\":\"0xffffffec\",\"signed\":-20,\"unsigned\":4294967276}"
         );
         assert_eq!(
-            result.get(&32768).unwrap().value.to_string(),
+            json(&result.get(&32768).unwrap().value).to_string(),
             "{\"2\":{\"tag\":{\"field\":2,\"tag_byte\":18,\"wire_type\":\"Len\"},\"value\":\"Test1234\"},\"3\":{\"tag\":{\"field\":3,\"tag_byte\":25,\"wire_type\":\"Fixed64\"},\"value\":{\"double\":2.1,\"signed\":4611911198408756429,\"unsigned\":4611911198408756429}}}"
         );
     }
@@ -0,0 +1,83 @@
+use crate::{
+    light::{Tag, WireType},
+    tags::{
+        fixed::{parse_fixed32, parse_fixed64},
+        length::parse_length_payload,
+        tag::get_tag_type,
+        var::parse_varint,
+    },
+};
+
+/// A field with its value left undecoded
+#[derive(Debug, Clone)]
+pub(crate) struct RawField<'a> {
+    pub(crate) tag: Tag,
+    pub(crate) value: RawValue<'a>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RawValue<'a> {
+    VarInt(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Len(&'a [u8]),
+    /**Deprecated groups and unknown wire types end parsing. Contains the remaining bytes */
+    Remaining(&'a [u8]),
+}
+
+/// Parse a single field without interpreting its value
+pub(crate) fn parse_raw_field(data: &[u8]) -> nom::IResult<&[u8], RawField<'_>> {
+    let (input, tag) = get_tag_type(data)?;
+    let (input, value) = match tag.wire_type {
+        WireType::VarInt => {
+            let (input, value) = parse_varint(input)?;
+            (input, RawValue::VarInt(value))
+        }
+        WireType::Fixed64 => {
+            let (input, value) = parse_fixed64(input)?;
+            (input, RawValue::Fixed64(value))
+        }
+        WireType::Fixed32 => {
+            let (input, value) = parse_fixed32(input)?;
+            (input, RawValue::Fixed32(value))
+        }
+        WireType::Len => {
+            let (input, value) = parse_length_payload(input)?;
+            (input, RawValue::Len(value))
+        }
+        WireType::StartGroup | WireType::EndGroup | WireType::Unknown => {
+            ([].as_slice(), RawValue::Remaining(input))
+        }
+    };
+
+    Ok((input, RawField { tag, value }))
+}
+
+/// Parse all fields of a message without interpreting their values
+pub(crate) fn parse_raw_fields(data: &[u8]) -> nom::IResult<&[u8], Vec<RawField<'_>>> {
+    let mut proto_data = data;
+    let mut fields = Vec::new();
+    while !proto_data.is_empty() {
+        let (input, field) = parse_raw_field(proto_data)?;
+        fields.push(field);
+        proto_data = input;
+    }
+    Ok((proto_data, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RawValue, parse_raw_fields};
+
+    #[test]
+    fn test_parse_raw_fields() {
+        let test = [8, 150, 1, 18, 3, 97, 98, 99, 29, 1, 0, 0, 0, 35, 1, 2];
+        let (remaining, result) = parse_raw_fields(&test).unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(result.len(), 4);
+        assert!(matches!(result[0].value, RawValue::VarInt(150)));
+        assert!(matches!(result[1].value, RawValue::Len(b"abc")));
+        assert!(matches!(result[2].value, RawValue::Fixed32(1)));
+        assert!(matches!(result[3].value, RawValue::Remaining([1, 2])));
+    }
+}
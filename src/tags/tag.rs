@@ -1,30 +1,22 @@
 use crate::{
     light::{Tag, WireType},
+    tags::var::parse_varint,
     utils::nom_helper::{Endian, nom_unsigned_one_byte},
 };
 
 /// Determine Protobuf Tag type
 pub(crate) fn get_tag_type(data: &[u8]) -> nom::IResult<&[u8], Tag> {
-    let (mut input, tag_byte) = nom_unsigned_one_byte(data, Endian::Le)?;
+    let (_, tag_byte) = nom_unsigned_one_byte(data, Endian::Le)?;
+    // Tags are varints. The field number is stored after the first three (3) bits
+    let (input, tag_value) = parse_varint(data)?;
     let field_number = 3;
 
-    let field = (tag_byte >> field_number) as usize;
-
-    let mut tag = Tag {
+    let tag = Tag {
         tag_byte,
         wire_type: get_wire_type(&tag_byte),
-        field,
+        field: (tag_value >> field_number) as usize,
     };
 
-    let mut check_msb = tag_byte;
-    // If Most significant bit is set. The next byte is part of the tag
-    while (check_msb >> 7) & 1 != 0 {
-        let (remaining, check) = nom_unsigned_one_byte(input, Endian::Le)?;
-        tag.field *= check as usize;
-        check_msb = check;
-        input = remaining;
-    }
-
     Ok((input, tag))
 }
 
@@ -73,4 +65,17 @@ mod tests {
         assert_eq!(result.wire_type, WireType::Len);
         assert_eq!(result.tag_byte, 10);
     }
+
+    #[test]
+    fn test_get_tag_type_multi_byte() {
+        // Field 300 with a Len wire type
+        let test = [226, 18, 1, 97];
+        let (remaining, result) = get_tag_type(&test).unwrap();
+        assert_eq!(result.field, 300);
+        assert_eq!(result.wire_type, WireType::Len);
+        assert_eq!(remaining.len(), 2);
+
+        // Truncated tag
+        assert!(get_tag_type(&[226]).is_err());
+    }
 }
@@ -1,31 +1,45 @@
 use crate::utils::nom_helper::{Endian, nom_unsigned_one_byte};
-use serde_json::Value;
+use nom::error::{Error, ErrorKind};
 
-/// Parse var based tags. Will be a number representing one of: int32, int64, uint32, uint64, sint32, sint64, bool, or enum
-pub(crate) fn parse_var(data: &[u8]) -> nom::IResult<&[u8], Value> {
+/// Parse a base 128 varint. Used for var values, tags and length prefixes. Var values are one of: int32, int64, uint32, uint64, sint32, sint64, bool, or enum
+pub(crate) fn parse_varint(data: &[u8]) -> nom::IResult<&[u8], u64> {
+    // Zero padding at the end of some records leaves the last value without any bytes. It is treated as 0
+    if data.is_empty() {
+        return Ok((data, 0));
+    }
     let mut proto_data = data;
-    let mut var_value: isize = 0;
+    let mut var_value: u64 = 0;
 
-    let mut shift = 0;
     let adjust = 0x7f;
     let wire = 7;
     let done = 0x80;
-    while !proto_data.is_empty() {
+    let max_bytes = 10;
+    for shift in 0..max_bytes {
+        // Fails if the data ends before the last byte of the varint
         let (input, value) = nom_unsigned_one_byte(proto_data, Endian::Le)?;
-        var_value += (value as isize & adjust) << (shift * wire);
-        shift += 1;
+        var_value |= (value as u64 & adjust).wrapping_shl(shift * wire);
 
         proto_data = input;
         if (value & done) == 0 {
-            break;
+            return Ok((proto_data, var_value));
         }
     }
-    Ok((proto_data, Value::Number(var_value.into())))
+    // Varints are at most ten (10) bytes
+    Err(nom::Err::Error(Error::new(data, ErrorKind::TooLarge)))
+}
+
+/// Decode a zigzag encoded signed integer. Used by sint32 and sint64
+pub(crate) fn zigzag(number: u64) -> i64 {
+    ((number >> 1) as i64) ^ -((number & 1) as i64)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_var;
+    use super::{parse_varint, zigzag};
+    use crate::{
+        light::ProtoValue,
+        render::json::{JsonOptions, value_to_json},
+    };
 
     #[test]
     fn test_parse_var() {
@@ -33,8 +47,33 @@ mod tests {
             240, 249, 7, 24, 61, 32, 1, 42, 10, 66, 105, 111, 109, 101, 65, 103, 101, 110, 116, 0,
             0, 0,
         ];
-        let (remaining, result) = parse_var(&test).unwrap();
+        let (remaining, result) = parse_varint(&test).unwrap();
         assert_eq!(remaining.len(), 19);
         assert_eq!(result, 130288);
     }
+
+    #[test]
+    fn test_parse_varint() {
+        let test = [255, 255, 255, 255, 255, 255, 255, 255, 255, 1];
+        let (remaining, result) = parse_varint(&test).unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(result, u64::MAX);
+
+        let value = ProtoValue::VarInt(result);
+        assert_eq!(value_to_json(&value, &JsonOptions::default()), -1);
+    }
+
+    #[test]
+    fn test_parse_varint_errors() {
+        // Ends in the middle of the varint and an eleventh byte
+        assert!(parse_varint(&[150]).is_err());
+        assert!(parse_varint(&[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1]).is_err());
+    }
+
+    #[test]
+    fn test_zigzag() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(1), -1);
+        assert_eq!(zigzag(4294967294), 2147483647);
+    }
 }
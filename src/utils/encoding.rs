@@ -13,6 +13,6 @@ mod tests {
     fn test_base64_encode_standard() {
         let test = b"Hello word!";
         let result = base64_encode_standard(test);
-        assert_eq!(result, "SGVsbG8gd29yZCE=")
+        assert_eq!(result, "SGVsbG8gd29yZCE=");
     }
 }
@@ -1,3 +1,4 @@
 pub(crate) mod encoding;
 pub(crate) mod nom_helper;
 pub(crate) mod strings;
+pub(crate) mod time;
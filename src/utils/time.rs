@@ -0,0 +1,97 @@
+/// Convert seconds and nanoseconds since the Unix epoch to a RFC 3339 UTC timestamp.
+/// Fractional seconds use 0, 3, 6 or 9 digits. Returns `None` for years outside of 1 to 9999
+pub(crate) fn unix_rfc3339(seconds: i64, nanos: u32) -> Option<String> {
+    let min = -62135596800;
+    let max = 253402300799;
+    let max_nanos = 999_999_999;
+    if !(min..=max).contains(&seconds) || nanos > max_nanos {
+        return None;
+    }
+
+    let day_seconds = 86400;
+    let days = seconds.div_euclid(day_seconds);
+    let remaining = seconds.rem_euclid(day_seconds);
+    let (year, month, day) = civil_from_days(days);
+
+    let hour = remaining / 3600;
+    let minute = (remaining % 3600) / 60;
+    let second = remaining % 60;
+
+    Some(format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}{}Z",
+        fraction(nanos)
+    ))
+}
+
+/// Format nanoseconds as a fraction of a second using 0, 3, 6 or 9 digits
+pub(crate) fn fraction(nanos: u32) -> String {
+    let millis = 1_000_000;
+    let micros = 1_000;
+    if nanos == 0 {
+        String::new()
+    } else if nanos.is_multiple_of(millis) {
+        format!(".{:03}", nanos / millis)
+    } else if nanos.is_multiple_of(micros) {
+        format!(".{:06}", nanos / micros)
+    } else {
+        format!(".{nanos:09}")
+    }
+}
+
+/// Convert days since the Unix epoch to a year, month and day. Based on <https://howardhinnant.github.io/date_algorithms.html>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let era_days = 146097;
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(era_days);
+    let day_of_era = shifted.rem_euclid(era_days);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+
+    let year = year_of_era + era * 400;
+    if month <= 2 {
+        (year + 1, month, day)
+    } else {
+        (year, month, day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{civil_from_days, fraction, unix_rfc3339};
+
+    #[test]
+    fn test_unix_rfc3339() {
+        assert_eq!(unix_rfc3339(0, 0).unwrap(), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            unix_rfc3339(1732406400, 500_000_000).unwrap(),
+            "2024-11-24T00:00:00.500Z"
+        );
+        assert_eq!(
+            unix_rfc3339(-62135596800, 0).unwrap(),
+            "0001-01-01T00:00:00Z"
+        );
+        assert!(unix_rfc3339(253402300800, 0).is_none());
+    }
+
+    #[test]
+    fn test_fraction() {
+        assert_eq!(fraction(0), "");
+        assert_eq!(fraction(10_000), ".000010");
+        assert_eq!(fraction(1), ".000000001");
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+}
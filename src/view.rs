@@ -0,0 +1,336 @@
+use crate::{
+    error::SunlightError,
+    light::{ExtractOptions, ProtoValue, Tag, WireType},
+    tags::{
+        length::length_value,
+        raw::{RawValue, parse_raw_field},
+        tag::get_tag_type,
+        var::zigzag,
+    },
+    utils::strings::borrow_utf8_str,
+};
+use log::error;
+
+/// A Protobuf message borrowed from the provided bytes. Nothing is decoded until a field is requested
+///
+/// # Example
+/// ```rust
+/// use sunlight::view::MessageView;
+///
+/// let proto_bytes = [8, 150, 1, 18, 3, 97, 98, 99];
+/// let message = MessageView::new(&proto_bytes);
+/// assert_eq!(message.get(1).unwrap().unwrap().as_u64(), Some(150));
+/// assert_eq!(message.get(2).unwrap().unwrap().as_str(), Some("abc"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MessageView<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        MessageView { data, offset: 0 }
+    }
+
+    /// Create a view of a message found at offset in the original bytes
+    pub(crate) fn at(data: &'a [u8], offset: usize) -> Self {
+        MessageView { data, offset }
+    }
+
+    /// Get the bytes of the message
+    pub fn bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Position of the message in the original bytes
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Iterate through every field in the order they appear. Iteration stops after the first error
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            data: self.data,
+            offset: self.offset,
+            failed: false,
+        }
+    }
+
+    /// Get a field by number. If the field appears more than once the last value is returned
+    pub fn get(&self, field: usize) -> Result<Option<FieldView<'a>>, SunlightError> {
+        let mut found = None;
+        for entry in self.fields() {
+            let entry = entry?;
+            if entry.field() == field {
+                found = Some(entry);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Get every value of a field by number
+    pub fn get_all(
+        &self,
+        field: usize,
+    ) -> impl Iterator<Item = Result<FieldView<'a>, SunlightError>> + use<'a> {
+        self.fields().filter(move |entry| match entry {
+            Ok(value) => value.field() == field,
+            Err(_) => true,
+        })
+    }
+}
+
+impl<'a> IntoIterator for MessageView<'a> {
+    type Item = Result<FieldView<'a>, SunlightError>;
+    type IntoIter = Fields<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields()
+    }
+}
+
+/// Iterate through the top level fields of the provided Protobuf bytes one at a time
+///
+/// # Example
+/// ```rust
+/// let proto_bytes = [16, 1, 24, 1, 74, 7, 49, 46, 49, 49, 52, 46, 48, 88, 1];
+/// let version = sunlight::view::iter_fields(&proto_bytes)
+///     .filter_map(Result::ok)
+///     .find_map(|field| if field.field() == 9 { field.as_str() } else { None });
+/// assert_eq!(version, Some("1.114.0"));
+/// ```
+pub fn iter_fields(data: &[u8]) -> Fields<'_> {
+    MessageView::new(data).fields()
+}
+
+/// Iterator over the fields of a `MessageView`
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    data: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<FieldView<'a>, SunlightError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.failed {
+            return None;
+        }
+
+        match parse_raw_field(self.data) {
+            Ok((input, field)) => {
+                let raw = &self.data[..self.data.len() - input.len()];
+                let offset = self.offset;
+                self.data = input;
+                self.offset += raw.len();
+                Some(Ok(FieldView {
+                    tag: field.tag,
+                    value: field.value,
+                    raw,
+                    offset,
+                }))
+            }
+            Err(err) => {
+                error!("[sunlight] could not parse protobuf field: {err:?}");
+                self.failed = true;
+                Some(Err(SunlightError::Parser))
+            }
+        }
+    }
+}
+
+/// A single field value borrowed from the message bytes. Values are only decoded when requested
+#[derive(Debug, Clone)]
+pub struct FieldView<'a> {
+    tag: Tag,
+    pub(crate) value: RawValue<'a>,
+    raw: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FieldView<'a> {
+    pub fn tag(&self) -> &Tag {
+        &self.tag
+    }
+
+    pub fn field(&self) -> usize {
+        self.tag.field
+    }
+
+    pub fn wire_type(&self) -> &WireType {
+        &self.tag.wire_type
+    }
+
+    /// Exact bytes of the tag and value
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Position of the tag in the original bytes
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Exact bytes of the value without the tag. Includes the length prefix for length prefixed values
+    pub fn value_bytes(&self) -> &'a [u8] {
+        get_tag_type(self.raw).map_or(self.raw, |(input, _)| input)
+    }
+
+    /// Get a numeric value as unsigned. Works for varint and fixed values
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value {
+            RawValue::VarInt(number) | RawValue::Fixed64(number) => Some(number),
+            RawValue::Fixed32(number) => Some(u64::from(number)),
+            RawValue::Len(_) | RawValue::Remaining(_) => None,
+        }
+    }
+
+    /// Get a numeric value as signed. Use `as_sint` for zigzag encoded values
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.value {
+            RawValue::VarInt(number) | RawValue::Fixed64(number) => Some(number as i64),
+            RawValue::Fixed32(number) => Some(i64::from(number as i32)),
+            RawValue::Len(_) | RawValue::Remaining(_) => None,
+        }
+    }
+
+    /// Get a sint32 or sint64 value
+    pub fn as_sint(&self) -> Option<i64> {
+        match self.value {
+            RawValue::VarInt(number) => Some(zigzag(number)),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.value {
+            RawValue::VarInt(number) => Some(number != 0),
+            _ => None,
+        }
+    }
+
+    /// Get a double or float value
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.value {
+            RawValue::Fixed64(bits) => Some(f64::from_bits(bits)),
+            RawValue::Fixed32(bits) => Some(f64::from(f32::from_bits(bits))),
+            _ => None,
+        }
+    }
+
+    /// Get the payload of a length prefixed value
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.value {
+            RawValue::Len(payload) => Some(payload),
+            _ => None,
+        }
+    }
+
+    /// Get a length prefixed value as a UTF8 string. Trailing NULL characters are removed
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(borrow_utf8_str)
+    }
+
+    /// Get a length prefixed value as a sub-message. The payload is not checked until its fields are read
+    pub fn as_message(&self) -> Option<MessageView<'a>> {
+        let payload = self.as_bytes()?;
+        Some(MessageView::at(
+            payload,
+            self.offset + self.raw.len() - payload.len(),
+        ))
+    }
+
+    /// Iterate through the fields of a length prefixed value as a sub-message
+    pub fn submessage(&self) -> Option<Fields<'a>> {
+        self.as_message().map(|message| message.fields())
+    }
+
+    /// Decode the value the same way as `extract_protobuf`
+    pub fn to_value(&self) -> ProtoValue {
+        match self.value {
+            RawValue::VarInt(number) => ProtoValue::VarInt(number),
+            RawValue::Fixed64(bits) => ProtoValue::Fixed64(bits),
+            RawValue::Fixed32(bits) => ProtoValue::Fixed32(bits),
+            RawValue::Len(payload) => length_value(payload, 0, &ExtractOptions::default()),
+            RawValue::Remaining(remaining) => ProtoValue::Group(remaining.to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldView, MessageView, iter_fields};
+    use crate::light::{ProtoValue, WireType};
+
+    #[test]
+    fn test_message_view() {
+        // Field 1 is repeated, field 2 is a sub-message and field 3 is a float
+        let test = [8, 1, 8, 3, 18, 5, 10, 3, 97, 98, 99, 29, 0, 0, 192, 63];
+        let message = MessageView::new(&test);
+        assert_eq!(message.fields().count(), 4);
+
+        let values: Vec<u64> = message
+            .get_all(1)
+            .map(|entry| entry.unwrap().as_u64().unwrap())
+            .collect();
+        assert_eq!(values, [1, 3]);
+        assert_eq!(message.get(1).unwrap().unwrap().as_u64(), Some(3));
+
+        let sub = message.get(2).unwrap().unwrap();
+        assert_eq!(sub.wire_type(), &WireType::Len);
+        let nested = sub.as_message().unwrap().get(1).unwrap().unwrap();
+        assert_eq!(nested.as_str(), Some("abc"));
+        assert_eq!(nested.offset(), 6);
+        assert_eq!(nested.raw(), [10, 3, 97, 98, 99]);
+        assert!(matches!(sub.to_value(), ProtoValue::String(_)));
+
+        assert_eq!(message.get(3).unwrap().unwrap().as_f64(), Some(1.5));
+        assert!(message.get(4).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_iter_fields() {
+        let test = [8, 150, 1, 18, 5, 10, 3, 8, 150, 1, 24, 2];
+        let fields: Vec<FieldView<'_>> = iter_fields(&test).map(Result::unwrap).collect();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].value_bytes(), [150, 1]);
+        assert_eq!(fields[1].offset(), 3);
+        assert_eq!(fields[1].value_bytes(), [5, 10, 3, 8, 150, 1]);
+        assert_eq!(fields[2].offset(), 10);
+
+        // Field 2 contains field 1, which contains field 1
+        let nested = fields[1].submessage().unwrap().next().unwrap().unwrap();
+        assert_eq!(nested.offset(), 5);
+        let inner: Vec<u64> = nested
+            .submessage()
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.as_u64())
+            .collect();
+        assert_eq!(inner, [150]);
+        assert!(fields[0].submessage().is_none());
+    }
+
+    #[test]
+    fn test_field_view_signed() {
+        let test = [8, 3, 21, 255, 255, 255, 255];
+        let message = MessageView::new(&test);
+        assert_eq!(message.get(1).unwrap().unwrap().as_sint(), Some(-2));
+        assert_eq!(message.get(2).unwrap().unwrap().as_i64(), Some(-1));
+        assert_eq!(message.get(2).unwrap().unwrap().as_u64(), Some(4294967295));
+    }
+
+    #[test]
+    fn test_message_view_bad_data() {
+        // Length prefix is larger than the remaining data
+        let test = [8, 1, 18, 10, 97];
+        let message = MessageView::new(&test);
+        let results: Vec<_> = message.fields().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(message.get(1).is_err());
+    }
+}
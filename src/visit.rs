@@ -0,0 +1,172 @@
+use crate::{
+    error::SunlightError,
+    view::{FieldView, MessageView},
+};
+
+/// What the parser should do after a field is visited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visit {
+    /**Move to the next field */
+    Continue,
+    /**Parse the length prefixed value as a sub-message. Ignored for other wire types */
+    Enter,
+    /**Stop parsing */
+    Stop,
+}
+
+/// Callbacks for single pass Protobuf parsing. Sub-messages are only parsed if `field` returns `Visit::Enter`
+pub trait Visitor<'a> {
+    /// Called before the fields of a sub-message
+    fn enter_message(&mut self, _field: &FieldView<'a>) {}
+    /// Called for every field in the order they appear
+    fn field(&mut self, field: &FieldView<'a>) -> Visit;
+    /// Called after the fields of a sub-message. Also called if the sub-message could not be parsed
+    fn exit_message(&mut self, _field: &FieldView<'a>) {}
+    /// Called when a field could not be parsed. Parsing of the current message ends
+    fn error(&mut self, _offset: usize, _error: &SunlightError) {}
+}
+
+/// Parse the provided Protobuf bytes and pass each field to the visitor
+///
+/// # Example
+/// ```rust
+/// use sunlight::{
+///     view::FieldView,
+///     visit::{Visit, Visitor, visit_protobuf},
+/// };
+///
+/// // Find the first string in field 5
+/// struct FindName<'a> {
+///     name: Option<&'a str>,
+/// }
+///
+/// impl<'a> Visitor<'a> for FindName<'a> {
+///     fn field(&mut self, field: &FieldView<'a>) -> Visit {
+///         if field.field() != 5 {
+///             return Visit::Continue;
+///         }
+///         self.name = field.as_str();
+///         Visit::Stop
+///     }
+/// }
+///
+/// let proto_bytes = [32, 1, 42, 10, 66, 105, 111, 109, 101, 65, 103, 101, 110, 116];
+/// let mut visitor = FindName { name: None };
+/// visit_protobuf(&proto_bytes, &mut visitor);
+/// assert_eq!(visitor.name, Some("BiomeAgent"));
+/// ```
+pub fn visit_protobuf<'a, V: Visitor<'a>>(data: &'a [u8], visitor: &mut V) {
+    walk(MessageView::new(data), visitor);
+}
+
+/// Visit each field of a message. Returns false if the visitor stopped parsing
+pub(crate) fn walk<'a, V: Visitor<'a>>(message: MessageView<'a>, visitor: &mut V) -> bool {
+    let mut position = message.offset();
+    for entry in message.fields() {
+        let field = match entry {
+            Ok(result) => result,
+            Err(err) => {
+                visitor.error(position, &err);
+                return true;
+            }
+        };
+        position = field.offset() + field.raw().len();
+
+        match visitor.field(&field) {
+            Visit::Continue => {}
+            Visit::Stop => return false,
+            Visit::Enter => {
+                let Some(sub) = field.as_message() else {
+                    continue;
+                };
+                visitor.enter_message(&field);
+                let keep_going = walk(sub, visitor);
+                visitor.exit_message(&field);
+                if !keep_going {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Visit, Visitor, visit_protobuf};
+    use crate::{error::SunlightError, view::FieldView};
+
+    #[derive(Default)]
+    struct Counter {
+        fields: usize,
+        messages: usize,
+        errors: Vec<usize>,
+        enter: bool,
+    }
+
+    impl<'a> Visitor<'a> for Counter {
+        fn enter_message(&mut self, _field: &FieldView<'a>) {
+            self.messages += 1;
+        }
+
+        fn field(&mut self, field: &FieldView<'a>) -> Visit {
+            self.fields += 1;
+            if self.enter && field.as_str().is_none() {
+                return Visit::Enter;
+            }
+            Visit::Continue
+        }
+
+        fn error(&mut self, offset: usize, _error: &SunlightError) {
+            self.errors.push(offset);
+        }
+    }
+
+    #[test]
+    fn test_visit_protobuf() {
+        // Field 2 is a sub-message containing a sub-message
+        let test = [8, 1, 18, 5, 10, 3, 8, 150, 1, 24, 2];
+
+        let mut skip = Counter::default();
+        visit_protobuf(&test, &mut skip);
+        assert_eq!(skip.fields, 3);
+        assert_eq!(skip.messages, 0);
+
+        let mut enter = Counter {
+            enter: true,
+            ..Default::default()
+        };
+        visit_protobuf(&test, &mut enter);
+        assert_eq!(enter.fields, 5);
+        assert_eq!(enter.messages, 2);
+        assert!(enter.errors.is_empty());
+    }
+
+    #[test]
+    fn test_visit_protobuf_error() {
+        // The sub-message has a length prefix larger than its payload
+        let test = [18, 2, 10, 200, 24, 2];
+        let mut visitor = Counter {
+            enter: true,
+            ..Default::default()
+        };
+        visit_protobuf(&test, &mut visitor);
+        assert_eq!(visitor.fields, 2);
+        assert_eq!(visitor.errors, [2]);
+    }
+
+    #[test]
+    fn test_visit_protobuf_stop() {
+        struct First(usize);
+        impl<'a> Visitor<'a> for First {
+            fn field(&mut self, field: &FieldView<'a>) -> Visit {
+                self.0 = field.field();
+                Visit::Stop
+            }
+        }
+
+        let mut visitor = First(0);
+        visit_protobuf(&[16, 1, 24, 1], &mut visitor);
+        assert_eq!(visitor.0, 2);
+    }
+}
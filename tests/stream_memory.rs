@@ -0,0 +1,58 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use sunlight::render::stream::stream_protobuf;
+
+/// Tracks the current and peak number of allocated bytes. Kept in its own test binary so no other test allocates at the same time
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        // SAFETY: Forwards the caller's layout to the system allocator
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        // SAFETY: The pointer was allocated by `alloc` above with the same layout
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+/// Peak bytes allocated while streaming a sub-message with `count` varint fields
+fn stream_peak(count: usize) -> usize {
+    let fields = [8, 150, 1].repeat(count);
+    let mut data = vec![18];
+    let mut length = fields.len();
+    while length >= 0x80 {
+        data.push((length as u8) | 0x80);
+        length >>= 7;
+    }
+    data.push(length as u8);
+    data.extend_from_slice(&fields);
+
+    let start = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(start, Ordering::SeqCst);
+    let mut serializer = serde_json::Serializer::new(std::io::sink());
+    stream_protobuf(&data, &mut serializer).unwrap();
+    PEAK.load(Ordering::SeqCst) - start
+}
+
+#[test]
+fn test_stream_memory_independent_of_size() {
+    let small = stream_peak(100);
+    let large = stream_peak(100_000);
+    assert!(
+        large <= small,
+        "streaming allocated {large} bytes, expected at most {small}"
+    );
+}